@@ -25,9 +25,10 @@ mod test {
     use anyhow::Result;
     use fake::{Fake, faker::internet::en::FreeEmail};
     use model::{
-        CREATE_WALLET, GET_USERS, GET_WALLETS, NON_EXISTING_ENDPOINT, REGISTER, User, Wallet, WalletType,
+        CREATE_WALLET, GET_USERS, GET_WALLETS, LOGIN, NON_EXISTING_ENDPOINT, PublicUser, REGISTER, User,
+        Wallet, WalletType,
     };
-    use sercli::{DateTime, Decimal, client::API};
+    use sercli::{DateTime, Decimal, Login, Pagination, REFRESH_TOKEN, client::API};
     use server::make_server;
 
     #[tokio::test]
@@ -58,9 +59,44 @@ mod test {
             birthday: DateTime::parse_from_str(datetime_str, format)?.into(),
         };
 
-        let (token, _user) = REGISTER.send(peter.clone()).await?;
+        let (tokens, _user) = REGISTER.send(peter.clone()).await?;
 
-        API::set_access_token(token);
+        let tokens = REFRESH_TOKEN.send(tokens.refresh_token).await?;
+
+        API::set_access_token(tokens.access_token);
+
+        let wrong_password = LOGIN
+            .send(Login {
+                login:    peter.email.clone(),
+                password: "wrong".to_string(),
+            })
+            .await
+            .expect_err("Login with wrong password should have failed");
+
+        let unknown_login = LOGIN
+            .send(Login {
+                login:    FreeEmail().fake(),
+                password: peter.password.clone(),
+            })
+            .await
+            .expect_err("Login of unknown user should have failed");
+
+        assert_eq!(
+            format!("{wrong_password}"),
+            "Something went wrong: Invalid login or password"
+        );
+        assert_eq!(format!("{wrong_password}"), format!("{unknown_login}"));
+
+        let (tokens, logged_in) = LOGIN
+            .send(Login {
+                login:    peter.email.clone(),
+                password: peter.password.clone(),
+            })
+            .await?;
+
+        assert_eq!(logged_in.email, peter.email);
+
+        API::set_access_token(tokens.access_token);
 
         let error = REGISTER
             .send(peter.clone())
@@ -81,11 +117,10 @@ mod test {
 
         assert_eq!(
             user,
-            User {
+            PublicUser {
                 id:       user.id,
                 email:    EMAIL.get_or_init(|| FreeEmail().fake::<String>()).clone(),
                 age:      20,
-                password: "prostaf".to_string(),
                 birthday: peter.birthday,
             }
         );
@@ -102,7 +137,12 @@ mod test {
 
         assert!(wallet.id != 0 && wallet.user_id != 0);
 
-        assert_eq!(GET_WALLETS.await?, vec![wallet]);
+        let wallets = GET_WALLETS.send(Pagination::default()).await?;
+
+        assert_eq!(wallets.total, 2);
+        assert_eq!(wallets.items[0].name, "Main");
+        assert_eq!(wallets.items[0].user_id, wallet.user_id);
+        assert_eq!(wallets.items[1], wallet);
 
         Ok(())
     }
@@ -4,17 +4,22 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use inflector::{Inflector, string::singularize::to_singular};
-use sqlparser::ast::{AlterTableOperation, CreateTable, ObjectName, ObjectNamePart};
+use sqlparser::ast::{
+    AlterColumnOperation, AlterTableOperation, CreateTable, Ident, ObjectName, ObjectNamePart,
+    TableConstraint,
+};
 
-use crate::field::Field;
+use crate::{field::Field, search::Search};
 
 #[derive(Debug, PartialEq)]
 pub struct Entity {
     pub name:       String,
     pub table_name: String,
     pub fields:     Vec<Field>,
+    /// Columns of composite `PRIMARY KEY (a, b)` constraint
+    pub key:        Vec<String>,
 }
 
 impl Entity {
@@ -28,13 +33,18 @@ impl Entity {
         Ok(())
     }
 
-    pub(crate) fn process_alter_table_operations(&mut self, operations: Vec<AlterTableOperation>) {
+    pub(crate) fn process_alter_table_operations(
+        &mut self,
+        operations: Vec<AlterTableOperation>,
+    ) -> Result<()> {
         for op in operations {
-            self.process_alter_table_operation(op);
+            self.process_alter_table_operation(op)?;
         }
+
+        Ok(())
     }
 
-    fn process_alter_table_operation(&mut self, operation: AlterTableOperation) {
+    fn process_alter_table_operation(&mut self, operation: AlterTableOperation) -> Result<()> {
         match operation {
             AlterTableOperation::AddColumn {
                 column_keyword: _,
@@ -42,9 +52,46 @@ impl Entity {
                 column_def,
                 column_position: _,
             } => self.fields.push(column_def.into()),
+            AlterTableOperation::DropColumn { column_name, .. } => {
+                self.fields.retain(|field| field.name != column_name.value);
+            }
+            AlterTableOperation::AlterColumn { column_name, op } => {
+                let Some(field) = self.fields.iter_mut().find(|field| field.name == column_name.value) else {
+                    bail!("Column {column_name} doesn't exist in {}", self.table_name)
+                };
+
+                match op {
+                    AlterColumnOperation::SetNotNull => field.set_nullable(false),
+                    AlterColumnOperation::DropNotNull => field.set_nullable(true),
+                    AlterColumnOperation::SetDataType { data_type, .. } => field.set_data_type(data_type),
+                    AlterColumnOperation::SetDefault { .. } | AlterColumnOperation::DropDefault => {}
+                    AlterColumnOperation::AddGenerated { .. } => {
+                        bail!("Unsupported alter column operation: {op}")
+                    }
+                }
+            }
+            AlterTableOperation::AddConstraint(TableConstraint::PrimaryKey { columns, .. }) => {
+                self.set_key(columns);
+            }
             AlterTableOperation::AddConstraint(_) => {}
-            _ => unimplemented!("Unsipported alter table operation: {operation}"),
+            _ => bail!("Unsupported alter table operation: {operation}"),
+        }
+
+        Ok(())
+    }
+
+    fn set_key(&mut self, columns: Vec<Ident>) {
+        let key: Vec<String> = columns.into_iter().map(|column| column.value).collect();
+
+        if key == ["id"] {
+            return;
         }
+
+        for field in self.fields.iter_mut().filter(|field| key.contains(&field.name)) {
+            field.set_nullable(false);
+        }
+
+        self.key = key;
     }
 
     pub(crate) fn to_code(&self) -> String {
@@ -52,10 +99,91 @@ impl Entity {
 
         let mut fields = String::new();
 
-        for field in &self.fields {
+        // Generated `tsvector` search columns are not loaded into entities
+        for field in self.fields.iter().filter(|field| field.sql_type != "tsvector") {
             fields.push_str(&field.to_code());
         }
 
+        let mut methods = vec![];
+
+        if !self.key.is_empty() {
+            let key: Vec<_> = self
+                .key
+                .iter()
+                .map(|column| format!("Self::{}", column.to_screaming_snake_case()))
+                .collect();
+
+            methods.push(format!(
+                r"    fn key() -> Vec<reflected::Field<Self>> {{
+        vec![{}]
+    }}",
+                key.join(", ")
+            ));
+        }
+
+        let uuid_fields: Vec<_> = self.fields.iter().filter(|field| field.ty == "Uuid").collect();
+
+        if uuid_fields.iter().any(|field| field.name == "id") {
+            methods.push(
+                r"    fn primary_key() -> PrimaryKey {
+        PrimaryKey::Uuid
+    }"
+                .to_string(),
+            );
+        }
+
+        // `Reflected` can't read `Uuid` ids and foreign keys
+        if !uuid_fields.is_empty() {
+            let arms: String = uuid_fields
+                .iter()
+                .map(|field| format!("            \"{0}\" => Some(self.{0}),\n", field.name))
+                .collect();
+
+            methods.push(format!(
+                r"    fn uuid_value(&self, field: reflected::Field<Self>) -> Option<Uuid> {{
+        match field.name {{
+{arms}            _ => None,
+        }}
+    }}"
+            ));
+        }
+
+        // Search column is generated back by `search_fields` and `search_config`
+        let search = self
+            .fields
+            .iter()
+            .filter(|field| field.sql_type == "tsvector")
+            .find_map(|field| field.generated.as_ref().and_then(Search::parse));
+
+        if let Some(search) = search {
+            let fields: Vec<_> = search
+                .columns
+                .iter()
+                .map(|column| format!("Self::{}", column.to_screaming_snake_case()))
+                .collect();
+
+            methods.push(format!(
+                r"    fn search_fields() -> Vec<reflected::Field<Self>> {{
+        vec![{}]
+    }}",
+                fields.join(", ")
+            ));
+
+            if let Some(config) = search.config.filter(|config| config != "simple") {
+                methods.push(format!(
+                    r#"    fn search_config() -> &'static str {{
+        "{config}"
+    }}"#
+                ));
+            }
+        }
+
+        let entity_impl = if methods.is_empty() {
+            format!("impl Entity for {name} {{}}")
+        } else {
+            format!("impl Entity for {name} {{\n{}\n}}", methods.join("\n\n"))
+        };
+
         format!(
             r"
 #[allow(unused_imports)]
@@ -78,6 +206,8 @@ mod reflected {{
 )]
 pub struct {name} {{
 {fields}}}
+
+{entity_impl}
 "
         )
     }
@@ -91,30 +221,40 @@ impl From<CreateTable> for Entity {
     fn from(value: CreateTable) -> Self {
         let table_name = format!("{}", value.name).replace('"', "");
 
-        Self {
+        let mut entity = Self {
             name: name_to_table_name(&table_name),
             table_name,
             fields: value.columns.into_iter().map(Into::into).collect(),
+            key: vec![],
+        };
+
+        for constraint in value.constraints {
+            if let TableConstraint::PrimaryKey { columns, .. } = constraint {
+                entity.set_key(columns);
+            }
         }
+
+        entity
     }
 }
 
 impl From<ObjectName> for Entity {
     fn from(value: ObjectName) -> Self {
-        if value.0.len() != 1 {
-            panic!("Check what is there");
-        }
-
-        let part = value.0.first().unwrap();
-
-        let table_name = match part {
-            ObjectNamePart::Identifier(ident) => ident.to_string().replace('"', ""),
-        };
+        // `schema.table` for tables outside of default schema
+        let table_name = value
+            .0
+            .iter()
+            .map(|part| match part {
+                ObjectNamePart::Identifier(ident) => ident.value.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(".");
 
         Self {
             name: name_to_table_name(&table_name),
             table_name,
             fields: vec![],
+            key: vec![],
         }
     }
 }
@@ -1,39 +1,77 @@
 use inflector::Inflector;
-use sqlparser::ast::{ColumnDef, ColumnOption, DataType};
+use sqlparser::ast::{ColumnDef, ColumnOption, DataType, Expr};
 
 #[derive(Debug, PartialEq)]
 pub struct Field {
-    pub name: String,
-    pub ty:   String,
+    pub name:      String,
+    pub ty:        String,
+    /// Type name as in `udt_name` of `information_schema.columns`
+    pub sql_type:  String,
+    pub nullable:  bool,
+    pub data_type: DataType,
+    /// Expression of `GENERATED ALWAYS AS (...) STORED` column
+    pub generated: Option<Expr>,
 }
 
 impl Field {
+    pub fn new(name: String, data_type: DataType, nullable: bool) -> Self {
+        let (ty, sql_type) = get_type(&data_type);
+
+        Self {
+            ty: if nullable { format!("Option<{ty}>") } else { ty },
+            name,
+            sql_type,
+            nullable,
+            data_type,
+            generated: None,
+        }
+    }
+
     pub fn to_code(&self) -> String {
         format!("    pub {}: {},\n", self.name, self.ty)
     }
+
+    pub(crate) fn set_data_type(&mut self, data_type: DataType) {
+        *self = Self {
+            generated: self.generated.take(),
+            ..Self::new(self.name.clone(), data_type, self.nullable)
+        };
+    }
+
+    pub(crate) fn set_nullable(&mut self, nullable: bool) {
+        *self = Self {
+            generated: self.generated.take(),
+            ..Self::new(self.name.clone(), self.data_type.clone(), nullable)
+        };
+    }
 }
 
 impl From<ColumnDef> for Field {
     fn from(value: ColumnDef) -> Self {
-        let non_null = value
-            .options
-            .iter()
-            .any(|option| matches!(option.option, ColumnOption::NotNull));
+        let non_null = value.options.iter().any(|option| {
+            matches!(
+                option.option,
+                ColumnOption::NotNull | ColumnOption::Unique { is_primary: true, .. }
+            )
+        });
+
+        let nullable = !non_null && value.name.to_string().replace('"', "") != "id";
 
-        let ty = get_type(
-            &value.data_type,
-            non_null || value.name.to_string().replace('"', "") == "id",
-        );
+        let generated = value.options.into_iter().find_map(|option| match option.option {
+            ColumnOption::Generated { generation_expr, .. } => generation_expr,
+            _ => None,
+        });
 
         Self {
-            name: value.name.value,
-            ty,
+            generated,
+            ..Self::new(value.name.value, value.data_type, nullable)
         }
     }
 }
 
-fn get_type(ty: &DataType, non_null: bool) -> String {
-    let tp: String = match ty {
+/// Rust type and Postgres type name of the column
+fn get_type(ty: &DataType) -> (String, String) {
+    let (tp, sql_type) = match ty {
         DataType::Custom(object_name, _) => {
             let name = object_name.0.first().unwrap_or_else(|| {
                 panic!("Empty object name: {object_name}");
@@ -43,21 +81,32 @@ fn get_type(ty: &DataType, non_null: bool) -> String {
                 panic!("Failed to convert object name to ident: {object_name}");
             };
 
-            if ident.value.to_lowercase() == "SERIAL".to_lowercase() {
-                "ID".into()
-            } else {
-                format!("crate::{}", ident.value.to_pascal_case())
+            match ident.value.to_lowercase().as_str() {
+                "serial" => ("ID", "int4"),
+                "bigserial" => ("i64", "int8"),
+                _ => {
+                    return (
+                        format!("crate::{}", ident.value.to_pascal_case()),
+                        ident.value.to_lowercase(),
+                    );
+                }
             }
         }
-        DataType::Varchar(_) => "String".into(),
-        DataType::SmallInt(_) => "i16".into(),
-        DataType::Integer(_) => "i32".into(),
-        DataType::Decimal(_) => "Decimal".into(),
-        DataType::Timestamp(_, _) => "DateTime".into(),
-        DataType::Real => "f32".into(),
-        DataType::Interval => "Duration".into(),
+        DataType::Varchar(_) => ("String", "varchar"),
+        DataType::Text => ("String", "text"),
+        DataType::SmallInt(_) | DataType::Int2(_) => ("i16", "int2"),
+        DataType::Integer(_) | DataType::Int(_) | DataType::Int4(_) => ("i32", "int4"),
+        DataType::BigInt(_) | DataType::Int8(_) => ("i64", "int8"),
+        DataType::Decimal(_) | DataType::Numeric(_) => ("Decimal", "numeric"),
+        DataType::Timestamp(_, _) => ("DateTime", "timestamp"),
+        DataType::Real | DataType::Float4 => ("f32", "float4"),
+        DataType::DoublePrecision | DataType::Float8 => ("f64", "float8"),
+        DataType::Bool | DataType::Boolean => ("bool", "bool"),
+        DataType::Interval => ("Duration", "interval"),
+        DataType::Uuid => ("Uuid", "uuid"),
+        DataType::Bytea => ("Bytes", "bytea"),
         _ => panic!("Unsupported date type: {ty:?}"),
     };
 
-    if non_null { tp } else { format!("Option<{tp}>") }
+    (tp.into(), sql_type.into())
 }
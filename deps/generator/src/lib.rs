@@ -3,5 +3,10 @@ mod field;
 mod generator;
 mod migrations;
 mod pg_enum;
+mod search;
 
+pub use entity::Entity;
+pub use field::Field;
 pub use generator::Generator;
+pub use migrations::Migrations;
+pub use pg_enum::PgEnum;
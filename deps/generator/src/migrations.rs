@@ -25,20 +25,31 @@ pub struct Migrations {
     pub enums:    BTreeMap<String, PgEnum>,
 }
 
-impl Migrations {}
-
 impl Migrations {
+    /// State of the database after all migrations in `model/migrations`
     pub fn get() -> Result<Self> {
-        let mut migrations = Self {
+        Self::parse(get_sql()?)
+    }
+
+    pub fn parse(migrations: impl IntoIterator<Item = impl AsRef<str>>) -> Result<Self> {
+        let mut result = Self {
             entities: BTreeMap::default(),
             enums:    BTreeMap::default(),
         };
 
-        for sql in get_sql()? {
-            migrations.process_migration(&sql)?;
+        for sql in migrations {
+            result.process_migration(sql.as_ref())?;
         }
 
-        Ok(migrations)
+        Ok(result)
+    }
+
+    pub fn table(&self, table_name: &str) -> Option<&Entity> {
+        self.entities.values().find(|entity| entity.table_name == table_name)
+    }
+
+    pub fn has_enum(&self, type_name: &str) -> bool {
+        self.enums.values().any(|en| en.table_name.replace('"', "") == type_name)
     }
 
     pub fn mod_code(&self) -> Result<String> {
@@ -71,13 +82,13 @@ pub use {mod_name}::*;
 
     fn process_migration(&mut self, sql: &str) -> Result<()> {
         for statement in Parser::parse_sql(&DIALECT, sql)? {
-            self.process_statement(statement);
+            self.process_statement(statement)?;
         }
 
         Ok(())
     }
 
-    fn process_statement(&mut self, statement: Statement) {
+    fn process_statement(&mut self, statement: Statement) -> Result<()> {
         match statement {
             Statement::CreateTable(create) => self.process_create_table(create),
             Statement::AlterTable {
@@ -87,11 +98,13 @@ pub use {mod_name}::*;
                 operations,
                 location,
                 on_cluster,
-            } => self.process_alter_table(name, if_exists, only, operations, location, on_cluster),
+            } => self.process_alter_table(name, if_exists, only, operations, location, on_cluster)?,
             Statement::CreateType { name, representation } => self.process_create_type(name, representation),
-            Statement::CreateIndex(_) => (),
+            Statement::CreateIndex(_) | Statement::CreateSchema { .. } => (),
             _ => unimplemented!("Unsupported statement: {statement:?}"),
         }
+
+        Ok(())
     }
 }
 
@@ -114,14 +127,14 @@ impl Migrations {
         operations: Vec<AlterTableOperation>,
         _location: Option<HiveSetLocation>,
         _on_cluster: Option<Ident>,
-    ) {
+    ) -> Result<()> {
         let entity: Entity = name.into();
 
         let Some(existing_entity) = self.entities.get_mut(&entity.name) else {
             panic!("Entity: {entity:?} doesn't exist yet to alter it")
         };
 
-        existing_entity.process_alter_table_operations(operations);
+        existing_entity.process_alter_table_operations(operations)
     }
 
     fn process_create_type(&mut self, name: ObjectName, representation: UserDefinedTypeRepresentation) {
@@ -153,6 +166,164 @@ fn get_sql() -> Result<impl Iterator<Item = String>> {
 
 #[cfg(test)]
 mod test {
+    use crate::migrations::Migrations;
+
+    #[test]
+    fn composite_key() -> anyhow::Result<()> {
+        let migrations = Migrations::parse([r#"
+CREATE TABLE "wallet_shares"
+(
+    "wallet_id" integer REFERENCES wallets (id),
+    "user_id"   integer,
+    "can_edit"  boolean NOT NULL,
+    "search"    tsvector GENERATED ALWAYS AS (to_tsvector('simple'::regconfig, user_id::TEXT)) STORED,
+    PRIMARY KEY ("wallet_id", "user_id")
+);"#])?;
+
+        let share = migrations.table("wallet_shares").unwrap();
+
+        assert_eq!(share.key, ["wallet_id", "user_id"]);
+        assert!(
+            share
+                .fields
+                .iter()
+                .filter(|field| field.name != "search")
+                .all(|field| !field.nullable)
+        );
+
+        assert!(share.to_code().contains(
+            r"pub struct WalletShare {
+    pub wallet_id: i32,
+    pub user_id: i32,
+    pub can_edit: bool,
+}
+
+impl Entity for WalletShare {
+    fn key() -> Vec<reflected::Field<Self>> {
+        vec![Self::WALLET_ID, Self::USER_ID]
+    }
+
+    fn search_fields() -> Vec<reflected::Field<Self>> {
+        vec![Self::USER_ID]
+    }
+}"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_fields() -> anyhow::Result<()> {
+        let migrations = Migrations::parse([
+            r#"
+CREATE TABLE "articles"
+(
+    "id"    serial PRIMARY KEY,
+    "title" varchar NOT NULL,
+    "body"  text
+);"#,
+            "ALTER TABLE articles ADD COLUMN search TSVECTOR GENERATED ALWAYS AS \
+             (to_tsvector('english'::regconfig, coalesce(title::TEXT, '') || ' ' || coalesce(body::TEXT, \
+             ''))) STORED;",
+        ])?;
+
+        let code = migrations.table("articles").unwrap().to_code();
+
+        assert!(!code.contains("pub search:"));
+        assert!(code.contains(
+            r#"impl Entity for Article {
+    fn search_fields() -> Vec<reflected::Field<Self>> {
+        vec![Self::TITLE, Self::BODY]
+    }
+
+    fn search_config() -> &'static str {
+        "english"
+    }
+}"#
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn uuid_fields() -> anyhow::Result<()> {
+        let migrations = Migrations::parse([r#"
+CREATE TABLE "planets"
+(
+    "id"   uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+    "name" varchar NOT NULL
+);
+CREATE TABLE "moons"
+(
+    "id"        serial PRIMARY KEY,
+    "planet_id" uuid NOT NULL REFERENCES planets (id)
+);"#])?;
+
+        assert!(migrations.table("planets").unwrap().to_code().contains(
+            r#"impl Entity for Planet {
+    fn primary_key() -> PrimaryKey {
+        PrimaryKey::Uuid
+    }
+
+    fn uuid_value(&self, field: reflected::Field<Self>) -> Option<Uuid> {
+        match field.name {
+            "id" => Some(self.id),
+            _ => None,
+        }
+    }
+}"#
+        ));
+
+        assert!(migrations.table("moons").unwrap().to_code().contains(
+            r#"impl Entity for Moon {
+    fn uuid_value(&self, field: reflected::Field<Self>) -> Option<Uuid> {
+        match field.name {
+            "planet_id" => Some(self.planet_id),
+            _ => None,
+        }
+    }
+}"#
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_alter_column() {
+        let Err(error) = Migrations::parse([
+            "CREATE TABLE counters (id integer NOT NULL);",
+            "ALTER TABLE counters ALTER COLUMN id ADD GENERATED ALWAYS AS IDENTITY;",
+        ]) else {
+            panic!("Unsupported alter column operation was accepted")
+        };
+
+        assert!(error.to_string().contains("Unsupported alter column operation"));
+    }
+
+    #[test]
+    fn alter_missing_column() {
+        let Err(error) = Migrations::parse([
+            "CREATE TABLE counters (id integer NOT NULL);",
+            "ALTER TABLE counters ALTER COLUMN value SET NOT NULL;",
+        ]) else {
+            panic!("Alter of missing column was accepted")
+        };
+
+        assert_eq!(error.to_string(), "Column value doesn't exist in counters");
+    }
+
+    #[test]
+    fn unsupported_alter_table() {
+        let Err(error) = Migrations::parse([
+            "CREATE TABLE counters (id integer NOT NULL);",
+            "ALTER TABLE counters RENAME TO totals;",
+        ]) else {
+            panic!("Unsupported alter table operation was accepted")
+        };
+
+        assert!(error.to_string().contains("Unsupported alter table operation"));
+    }
+
     // use crate::{entity::Entity, field::Field, migrations::Migrations};
 
     #[test]
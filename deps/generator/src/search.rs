@@ -0,0 +1,105 @@
+use sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, FunctionArguments, ObjectNamePart, Value,
+};
+
+/// Fields and config of generated `tsvector` search column:
+/// `to_tsvector('simple'::regconfig, coalesce(name::TEXT, '') || ' ' || ...)`
+#[derive(Debug, PartialEq)]
+pub(crate) struct Search {
+    pub config:  Option<String>,
+    pub columns: Vec<String>,
+}
+
+impl Search {
+    pub(crate) fn parse(expr: &Expr) -> Option<Self> {
+        let Expr::Function(function) = expr else {
+            return None;
+        };
+
+        if !function_name(function).eq_ignore_ascii_case("to_tsvector") {
+            return None;
+        }
+
+        let args = function_args(function);
+
+        let (config, text) = match args.as_slice() {
+            [text] => (None, *text),
+            [config, text] => (Some(string_value(config)?), *text),
+            _ => return None,
+        };
+
+        let mut columns = vec![];
+        collect_columns(text, &mut columns);
+
+        if columns.is_empty() {
+            return None;
+        }
+
+        Some(Self { config, columns })
+    }
+}
+
+fn function_name(function: &Function) -> String {
+    function
+        .name
+        .0
+        .last()
+        .and_then(ObjectNamePart::as_ident)
+        .map(|ident| ident.value.clone())
+        .unwrap_or_default()
+}
+
+fn function_args(function: &Function) -> Vec<&Expr> {
+    let FunctionArguments::List(list) = &function.args else {
+        return vec![];
+    };
+
+    list.args
+        .iter()
+        .filter_map(|arg| match arg {
+            FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => Some(expr),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `'english'` or `'english'::regconfig`
+fn string_value(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Value(value) => match &value.value {
+            Value::SingleQuotedString(string) => Some(string.clone()),
+            _ => None,
+        },
+        Expr::Cast { expr, .. } | Expr::Nested(expr) => string_value(expr),
+        _ => None,
+    }
+}
+
+/// Columns referenced by the expression in order of appearance
+fn collect_columns(expr: &Expr, columns: &mut Vec<String>) {
+    let mut push = |name: &String| {
+        if !columns.contains(name) {
+            columns.push(name.clone());
+        }
+    };
+
+    match expr {
+        Expr::Identifier(ident) => push(&ident.value),
+        Expr::CompoundIdentifier(idents) => {
+            if let Some(ident) = idents.last() {
+                push(&ident.value);
+            }
+        }
+        Expr::Cast { expr, .. } | Expr::Nested(expr) => collect_columns(expr, columns),
+        Expr::BinaryOp { left, right, .. } => {
+            collect_columns(left, columns);
+            collect_columns(right, columns);
+        }
+        Expr::Function(function) => {
+            for arg in function_args(function) {
+                collect_columns(arg, columns);
+            }
+        }
+        _ => {}
+    }
+}
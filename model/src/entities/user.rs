@@ -24,3 +24,5 @@ pub struct User {
     pub age: i32,
     pub birthday: Option<DateTime>,
 }
+
+impl Entity for User {}
@@ -24,3 +24,5 @@ pub struct Wallet {
     pub amount: Decimal,
     pub tp: crate::WalletType,
 }
+
+impl Entity for Wallet {}
@@ -1,9 +1,11 @@
 mod entities;
 mod requests;
 mod user;
+mod wallet;
 
 pub use entities::*;
 pub use requests::*;
+pub use user::PublicUser;
 
 #[cfg(test)]
 mod tests {
@@ -21,6 +23,34 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn schema_matches_migrations() -> Result<()> {
+        use sercli::{EntitySchema, db::prepare_db, verify_schemas};
+
+        use crate::{User, Wallet};
+
+        let pool = prepare_db().await?;
+
+        verify_schemas(&[EntitySchema::of::<User>(), EntitySchema::of::<Wallet>()], &pool).await
+    }
+
+    #[ignore]
+    #[test]
+    fn generate_migration() -> Result<()> {
+        use sercli::{EntitySchema, db::generate_migration};
+
+        use crate::{User, Wallet};
+
+        if let Some(path) = generate_migration(
+            "update_entities",
+            &[EntitySchema::of::<User>(), EntitySchema::of::<Wallet>()],
+        )? {
+            println!("Generated migration: {}", path.display());
+        }
+
+        Ok(())
+    }
+
     #[ignore]
     #[tokio::test]
     async fn wipe_db() -> Result<()> {
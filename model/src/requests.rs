@@ -1,11 +1,12 @@
-use sercli::client::Request;
+use sercli::{Login, Page, Pagination, TokenPair, client::Request};
 
-use crate::{Wallet, entities::User};
+use crate::{PublicUser, Wallet, entities::User};
 
-pub const REGISTER: Request<User, (String, User)> = Request::new("register");
-pub const GET_USERS: Request<(), Vec<User>> = Request::new("get_users");
+pub const REGISTER: Request<User, (TokenPair, PublicUser)> = Request::new("register");
+pub const LOGIN: Request<Login, (TokenPair, PublicUser)> = Request::new("login");
+pub const GET_USERS: Request<(), Vec<PublicUser>> = Request::new("get_users");
 
 pub const CREATE_WALLET: Request<Wallet, Wallet> = Request::new("create_wallet");
-pub const GET_WALLETS: Request<(), Vec<Wallet>> = Request::new("get_wallets");
+pub const GET_WALLETS: Request<Pagination, Page<Wallet>> = Request::new("get_wallets");
 
 pub const NON_EXISTING_ENDPOINT: Request<(), ()> = Request::new("non_existing_endpoint");
@@ -1,10 +1,18 @@
-use sercli::{ID, SercliUser};
+use anyhow::Result;
+use sercli::{Crud, DateTime, EntityId, ID, PgAcquire, SercliUser};
+use serde::{Deserialize, Serialize};
 
-use crate::entities::User;
+use crate::entities::{User, Wallet};
+
+impl User {
+    pub async fn wallets(&self, db: impl PgAcquire) -> Result<Vec<Wallet>> {
+        self.has_many(Wallet::USER_ID, db).await
+    }
+}
 
 impl SercliUser for User {
-    fn id(&self) -> ID {
-        self.id
+    fn id(&self) -> EntityId {
+        self.id.into()
     }
 
     fn password(&self) -> &str {
@@ -19,3 +27,23 @@ impl SercliUser for User {
         "email"
     }
 }
+
+/// `User` returned to clients. Password hash never leaves the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicUser {
+    pub id:       ID,
+    pub email:    String,
+    pub age:      i32,
+    pub birthday: Option<DateTime>,
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        Self {
+            id:       user.id,
+            email:    user.email,
+            age:      user.age,
+            birthday: user.birthday,
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use anyhow::Result;
+use sercli::{Crud, PgAcquire};
+
+use crate::entities::{User, Wallet};
+
+impl Wallet {
+    pub async fn user(&self, db: impl PgAcquire) -> Result<User> {
+        self.belongs_to(Wallet::USER_ID, db).await
+    }
+}
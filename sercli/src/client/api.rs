@@ -57,4 +57,9 @@ impl API {
     pub fn set_access_token(token: impl ToString) {
         Self::add_header("token", token);
     }
+
+    /// Shown in `Session::device_name` of sessions started by this client
+    pub fn set_device_name(name: impl ToString) {
+        Self::add_header("device-name", name);
+    }
 }
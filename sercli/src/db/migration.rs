@@ -0,0 +1,408 @@
+use std::{fs::write, path::PathBuf};
+
+use anyhow::{Result, bail};
+use generator::Migrations;
+use sercli_utils::git_root;
+
+use crate::{
+    Utc,
+    server::crud::{EntitySchema, ExpectedColumn, type_matches},
+};
+
+/// Writes `model/migrations/{timestamp}_{name}.sql` with statements bringing
+/// the schema created by existing migrations to the state of `schemas`.
+/// Returns `None` if migrations are up to date.
+pub fn generate_migration(name: &str, schemas: &[EntitySchema]) -> Result<Option<PathBuf>> {
+    let sql = migration_sql(schemas, &Migrations::get()?)?;
+
+    if sql.is_empty() {
+        return Ok(None);
+    }
+
+    let path = git_root()?
+        .join("model/migrations")
+        .join(format!("{}_{name}.sql", Utc::now().format("%Y%m%d%H%M%S")));
+
+    write(&path, sql)?;
+
+    Ok(Some(path))
+}
+
+/// `CREATE TABLE` for missing tables and `ALTER TABLE` for changed ones.
+/// Tables without entities in `schemas` are left as is. Primary key columns
+/// and enum types have to be changed manually. Added `NOT NULL` columns fill
+/// existing rows with empty value of their type.
+pub fn migration_sql(schemas: &[EntitySchema], migrations: &Migrations) -> Result<String> {
+    let mut statements = vec![];
+
+    for schema in schemas {
+        let table_name = schema.qualified_table_name();
+
+        for column in schema.columns.iter().filter(|column| column.enum_type) {
+            if !migrations.has_enum(&column.sql_type) {
+                bail!(
+                    "Enum type {} of {table_name}.{} is not created by migrations",
+                    column.sql_type,
+                    column.name
+                );
+            }
+        }
+
+        let Some(table) = migrations.table(&table_name) else {
+            statements.push(schema.create_table.clone());
+            continue;
+        };
+
+        for column in schema.columns.iter().filter(|column| !column.id) {
+            let Some(field) = table.fields.iter().find(|field| field.name == column.name) else {
+                statements.extend(add_column(&table_name, column)?);
+                statements.extend(column.index.clone());
+                continue;
+            };
+
+            if !type_matches(&column.sql_type, &field.sql_type) {
+                statements.push(format!(
+                    "ALTER TABLE {table_name} ALTER COLUMN {} TYPE {};",
+                    column.name, column.sql_type
+                ));
+            }
+
+            if field.nullable != column.nullable {
+                statements.push(format!(
+                    "ALTER TABLE {table_name} ALTER COLUMN {} {} NOT NULL;",
+                    column.name,
+                    if column.nullable { "DROP" } else { "SET" }
+                ));
+            }
+        }
+
+        for field in &table.fields {
+            if !schema.columns.iter().any(|column| column.name == field.name) {
+                statements.push(format!("ALTER TABLE {table_name} DROP COLUMN {};", field.name));
+            }
+        }
+    }
+
+    Ok(statements.join("\n"))
+}
+
+/// `NOT NULL` column without default gets temporary `DEFAULT` so it can be
+/// added to populated table
+fn add_column(table_name: &str, column: &ExpectedColumn) -> Result<Vec<String>> {
+    let add = format!("ALTER TABLE {table_name} ADD COLUMN {}", column.definition);
+
+    if column.nullable || column.default {
+        return Ok(vec![format!("{add};")]);
+    }
+
+    let fill = if column.constraint {
+        None
+    } else {
+        empty_value(&column.sql_type)
+    };
+
+    let Some(fill) = fill else {
+        bail!(
+            "NOT NULL column {table_name}.{} can't be filled in existing rows. Make the field optional or \
+             set its default with Column::default",
+            column.name
+        );
+    };
+
+    Ok(vec![
+        format!("{add} DEFAULT {fill};"),
+        format!(
+            "ALTER TABLE {table_name} ALTER COLUMN {} DROP DEFAULT;",
+            column.name
+        ),
+    ])
+}
+
+/// Value filling existing rows for added column of `sql_type`
+fn empty_value(sql_type: &str) -> Option<&'static str> {
+    let value = match sql_type {
+        "SMALLINT" | "INTEGER" | "BIGINT" | "REAL" | "DOUBLE PRECISION" | "NUMERIC" => "0",
+        "BOOLEAN" => "false",
+        "TIMESTAMP" => "now()",
+        "INTERVAL" => "'0'",
+        "UUID" => "gen_random_uuid()",
+        "TEXT" | "BYTEA" => "''",
+        sql_type if sql_type.starts_with("VARCHAR") => "''",
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use generator::Migrations;
+    use reflected::{Field, Reflected};
+    use sqlx::{Executor, FromRow};
+
+    use crate::{
+        Column, Crud, DateTime, Entity, EntitySchema,
+        db::{migration_sql, prepare_db},
+        verify_schemas,
+    };
+
+    #[derive(Default, Reflected, FromRow)]
+    struct Gardener {
+        id:       i32,
+        name:     String,
+        age:      i64,
+        email:    String,
+        nickname: Option<String>,
+        hired:    DateTime,
+    }
+
+    impl Entity for Gardener {
+        fn columns() -> Vec<Column<Self>> {
+            vec![Column::new(Gardener::NICKNAME).varchar(30)]
+        }
+    }
+
+    #[derive(Default, Reflected, FromRow)]
+    struct Greenhouse {
+        id:          i32,
+        gardener_id: i32,
+    }
+
+    impl Entity for Greenhouse {
+        fn columns() -> Vec<Column<Self>> {
+            vec![Column::new(Greenhouse::GARDENER_ID).references::<Gardener>().index()]
+        }
+    }
+
+    const GARDENERS: &str = r#"
+CREATE TABLE "gardeners"
+(
+    "id"     serial PRIMARY KEY,
+    "name"   varchar NOT NULL,
+    "age"    integer NOT NULL,
+    "email"  varchar,
+    "legacy" integer NOT NULL
+);
+ALTER TABLE gardeners
+    ADD COLUMN "hired" timestamp NOT NULL DEFAULT now();
+"#;
+
+    #[test]
+    fn migration() -> Result<()> {
+        let schemas = [EntitySchema::of::<Gardener>(), EntitySchema::of::<Greenhouse>()];
+
+        let sql = migration_sql(&schemas, &Migrations::parse([GARDENERS])?)?;
+
+        assert_eq!(
+            sql,
+            r"ALTER TABLE gardeners ALTER COLUMN age TYPE BIGINT;
+ALTER TABLE gardeners ALTER COLUMN email SET NOT NULL;
+ALTER TABLE gardeners ADD COLUMN nickname VARCHAR(30);
+ALTER TABLE gardeners DROP COLUMN legacy;
+CREATE TABLE IF NOT EXISTS greenhouses
+(
+   id SERIAL PRIMARY KEY,
+   gardener_id INTEGER NOT NULL REFERENCES gardeners (id)
+);
+CREATE INDEX IF NOT EXISTS greenhouses_gardener_id_idx ON greenhouses (gardener_id);"
+        );
+
+        let migrations = Migrations::parse([GARDENERS, &sql])?;
+
+        assert_eq!(migration_sql(&schemas, &migrations)?, "");
+
+        Ok(())
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, Reflected, FromRow)]
+    struct Sprout {
+        id:     i32,
+        name:   String,
+        height: i64,
+        potted: bool,
+        note:   Option<String>,
+    }
+
+    impl Entity for Sprout {}
+
+    const SPROUTS: &str = "CREATE TABLE sprouts (id SERIAL PRIMARY KEY, name TEXT NOT NULL);";
+
+    #[test]
+    fn not_null_column() -> Result<()> {
+        let schemas = [EntitySchema::of::<Sprout>()];
+
+        let sql = migration_sql(&schemas, &Migrations::parse([SPROUTS])?)?;
+
+        assert_eq!(
+            sql,
+            r"ALTER TABLE sprouts ADD COLUMN height BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE sprouts ALTER COLUMN height DROP DEFAULT;
+ALTER TABLE sprouts ADD COLUMN potted BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE sprouts ALTER COLUMN potted DROP DEFAULT;
+ALTER TABLE sprouts ADD COLUMN note TEXT;"
+        );
+
+        assert_eq!(migration_sql(&schemas, &Migrations::parse([SPROUTS, &sql])?)?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn unfillable_column() -> Result<()> {
+        #[derive(Default, Reflected, FromRow)]
+        struct Pot {
+            id:        i32,
+            sprout_id: i32,
+        }
+
+        impl Entity for Pot {
+            fn columns() -> Vec<Column<Self>> {
+                vec![Column::new(Pot::SPROUT_ID).references::<Sprout>()]
+            }
+        }
+
+        let migrations = Migrations::parse(["CREATE TABLE pots (id SERIAL PRIMARY KEY);"])?;
+
+        assert_eq!(
+            migration_sql(&[EntitySchema::of::<Pot>()], &migrations)
+                .unwrap_err()
+                .to_string(),
+            "NOT NULL column pots.sprout_id can't be filled in existing rows. Make the field optional or \
+             set its default with Column::default"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn not_null_column_of_populated_table() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Sprout::drop_table(&pool).await?;
+        pool.execute(SPROUTS).await?;
+        pool.execute("INSERT INTO sprouts (name) VALUES ('basil'), ('mint');").await?;
+
+        let schemas = [EntitySchema::of::<Sprout>()];
+
+        pool.execute(&*migration_sql(&schemas, &Migrations::parse([SPROUTS])?)?).await?;
+
+        verify_schemas(&schemas, &pool).await?;
+
+        let mut sprouts = Sprout::get_all(&pool).await?;
+        sprouts.sort_by_key(|sprout| sprout.id);
+
+        assert_eq!(
+            sprouts,
+            [
+                Sprout {
+                    id: 1,
+                    name: "basil".to_string(),
+                    ..Default::default()
+                },
+                Sprout {
+                    id: 2,
+                    name: "mint".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        Sprout::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn search_column() -> Result<()> {
+        #[derive(Default, Reflected, FromRow)]
+        struct Seed {
+            id:   i32,
+            name: String,
+        }
+
+        impl Entity for Seed {
+            fn search_fields() -> Vec<Field<Self>> {
+                vec![Seed::NAME]
+            }
+        }
+
+        let schemas = [EntitySchema::of::<Seed>()];
+
+        let create = "CREATE TABLE seeds (id SERIAL PRIMARY KEY, name TEXT NOT NULL);";
+
+        let sql = migration_sql(&schemas, &Migrations::parse([create])?)?;
+
+        assert_eq!(
+            sql,
+            "ALTER TABLE seeds ADD COLUMN search TSVECTOR GENERATED ALWAYS AS \
+             (to_tsvector('simple'::regconfig, coalesce(name::TEXT, ''))) STORED;
+CREATE INDEX IF NOT EXISTS seeds_search_idx ON seeds USING GIN (search);"
+        );
+
+        assert_eq!(migration_sql(&schemas, &Migrations::parse([create, &sql])?)?, "");
+
+        let created = Migrations::parse([Seed::create_table_query()])?;
+
+        assert_eq!(migration_sql(&schemas, &created)?, "");
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_enum() -> Result<()> {
+        #[derive(
+            strum::Display,
+            strum::EnumString,
+            serde::Serialize,
+            serde::Deserialize,
+            sqlx::Type,
+            Copy,
+            Clone,
+            Default,
+            PartialEq,
+            Debug,
+        )]
+        #[sqlx(type_name = "soil_type", rename_all = "lowercase")]
+        enum SoilType {
+            #[default]
+            Clay,
+            Sand,
+        }
+
+        impl reflected::ToReflectedVal<SoilType> for &str {
+            fn to_reflected_val(&self) -> std::result::Result<SoilType, String> {
+                use std::str::FromStr;
+                Ok(SoilType::from_str(self).unwrap())
+            }
+        }
+
+        #[derive(Default, Reflected, FromRow)]
+        struct Bed {
+            id:   i32,
+            soil: SoilType,
+        }
+
+        impl Entity for Bed {}
+
+        let schemas = [EntitySchema::of::<Bed>()];
+
+        assert_eq!(
+            migration_sql(&schemas, &Migrations::parse([""])?).unwrap_err().to_string(),
+            "Enum type soil_type of beds.soil is not created by migrations"
+        );
+
+        let migrations = Migrations::parse(["CREATE TYPE \"soil_type\" AS ENUM ('clay', 'sand');"])?;
+
+        assert_eq!(
+            migration_sql(&schemas, &migrations)?,
+            r"CREATE TABLE IF NOT EXISTS beds
+(
+   id SERIAL PRIMARY KEY,
+   soil soil_type NOT NULL
+);"
+        );
+
+        Ok(())
+    }
+}
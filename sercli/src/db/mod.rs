@@ -1,3 +1,5 @@
+mod migration;
+
 use std::{
     env::set_var,
     process::{Command, Stdio},
@@ -6,6 +8,7 @@ use std::{
 
 use anyhow::{Result, bail};
 use generator::Generator;
+pub use migration::*;
 use sercli_utils::git_root;
 use sqlx::{PgPool, migrate::Migrator, postgres::PgPoolOptions};
 use tokio::time::sleep;
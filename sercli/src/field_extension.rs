@@ -2,20 +2,20 @@
 
 use anyhow::Result;
 use reflected::Field;
-use sqlx::{Encode, PgPool, Postgres, Type};
+use sqlx::{Encode, Postgres, Type};
 
-use crate::Crud;
+use crate::{Crud, server::PgAcquire};
 
 pub trait FieldExtension<T: Crud>: Sized {
     async fn one_where<V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static>(
         &self,
         value: V,
-        pool: &PgPool,
+        db: impl PgAcquire,
     ) -> Result<Option<T>>;
     async fn all_where<V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static>(
         &self,
         value: V,
-        pool: &PgPool,
+        db: impl PgAcquire,
     ) -> Result<Vec<T>>;
 }
 
@@ -23,15 +23,15 @@ impl<T: Crud> FieldExtension<T> for Field<T> {
     async fn one_where<V: Encode<'static, Postgres> + Type<Postgres> + Send + 'static>(
         &self,
         value: V,
-        pool: &PgPool,
+        db: impl PgAcquire,
     ) -> Result<Option<T>> {
-        T::get(pool).with(*self, value).one().await
+        T::get(db).with(*self, value).one().await
     }
     async fn all_where<V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static>(
         &self,
         value: V,
-        pool: &PgPool,
+        db: impl PgAcquire,
     ) -> Result<Vec<T>> {
-        T::get(pool).with(*self, value).all().await
+        T::get(db).with(*self, value).all().await
     }
 }
@@ -1,15 +1,30 @@
+// `Reflected` derive refers to `sercli::DateTime` for date fields
+extern crate self as sercli;
+
 pub mod client;
 pub mod db;
 mod field_extension;
+mod login;
 mod password;
 pub mod server;
+mod tokens;
 mod user;
 
 pub use axum::{Json, extract::State, http::HeaderMap};
 pub use chrono::{Duration, NaiveDateTime as DateTime, Utc};
 pub use field_extension::FieldExtension;
+pub use login::Login;
 pub use password::{check_password, hash_password};
-pub use server::{connection_string_from_compose, crud::Crud, db_storage::DBStorage};
+pub use server::{
+    PgAcquire, connection_string_from_compose,
+    crud::{
+        Bytes, Column, ConcurrencyError, Crud, EntityId, EntitySchema, Order, Page, Pagination, PrimaryKey,
+        SchemaError, SchemaMismatch, Uuid, verify_schemas,
+    },
+    db_storage::DBStorage,
+    with_transaction,
+};
+pub use tokens::{REFRESH_TOKEN, Session, TokenPair};
 pub use user::SercliUser;
 
 pub use crate::server::crud::Entity;
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Input of login request registered with `Server::add_login`. The request
+/// is declared as `Request<Login, (TokenPair, PublicUser)>` where
+/// `PublicUser` is user type without password implementing `From<User>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Login {
+    /// Value of `SercliUser::login_field_name` field
+    pub login:    String,
+    pub password: String,
+}
@@ -1,60 +1,252 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
 use anyhow::{Result, anyhow, bail};
+use axum::{extract::ConnectInfo, http::request::Parts};
 use fake::Fake;
 use log::error;
 use pasetors::{
     Local,
     claims::{Claims, ClaimsValidationRules},
-    keys::SymmetricKey,
+    footer::Footer,
     local,
+    paserk::Id,
     token::UntrustedToken,
     version4::V4,
 };
-use sqlx::{Executor, FromRow, PgPool, query, query_as};
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, FromRow, PgPool, query, query_as, query_scalar};
 
-use crate::{DBStorage, ID, SercliUser, server::crud::Crud};
+use crate::{
+    EntityId, ID, SercliUser, Session, TokenPair,
+    server::{TokenKeys, crud::Crud},
+};
 
 #[derive(Debug, FromRow)]
 struct UserToken {
-    user_id: ID,
-    token:   String,
+    id:      ID,
+    /// Text representation of `EntityId` so users with any primary key type
+    /// can be stored
+    user_id: String,
+}
+
+/// Lifetimes of issued tokens configured with `Server::access_token_lifetime`
+/// and `Server::refresh_token_lifetime`
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct TokenLifetimes {
+    pub(crate) access:  Duration,
+    pub(crate) refresh: Duration,
+}
+
+impl Default for TokenLifetimes {
+    fn default() -> Self {
+        Self {
+            access:  Duration::from_secs(15 * 60),
+            refresh: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// Reverse proxies configured with `Server::trusted_proxy`
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TrustedProxies(pub(crate) Vec<IpAddr>);
+
+/// Client of a new session stored with its tokens
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SessionInfo {
+    pub(crate) user_agent:  Option<String>,
+    pub(crate) ip:          Option<String>,
+    pub(crate) device_name: Option<String>,
+}
+
+impl SessionInfo {
+    pub(crate) fn from_parts(parts: &Parts) -> Self {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+
+        let proxies = parts.extensions.get::<TrustedProxies>().cloned().unwrap_or_default();
+
+        let connected = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+
+        let ip = connected.map(|connected| {
+            if !proxies.0.contains(&connected) {
+                return connected.to_string();
+            }
+
+            // Each proxy appends address of its client so the last address not
+            // added by trusted proxies is the client. Earlier ones can be spoofed.
+            header("x-forwarded-for")
+                .and_then(|value| {
+                    value
+                        .rsplit(',')
+                        .map(str::trim)
+                        .find(|ip| !ip.parse().is_ok_and(|ip| proxies.0.contains(&ip)))
+                        .map(ToString::to_string)
+                })
+                .unwrap_or_else(|| connected.to_string())
+        });
+
+        Self {
+            user_agent: header("user-agent"),
+            ip,
+            device_name: header("device-name"),
+        }
+    }
 }
 
 pub(crate) struct AccessToken {}
 
 impl AccessToken {
-    pub async fn generate_token<User: SercliUser>(user: &User, pool: &PgPool) -> Result<String> {
-        Self::create_table(pool).await?;
+    /// Starts new session of `user` stored in `token_storage`
+    pub async fn generate_tokens<User: SercliUser>(
+        user: &User,
+        session: &SessionInfo,
+        lifetimes: TokenLifetimes,
+        keys: &TokenKeys,
+        pool: &PgPool,
+    ) -> Result<TokenPair> {
+        let token: String = 32.fake();
+        let refresh_token: String = 64.fake();
 
-        let key = Self::get_encryption_key(pool).await?;
-        let token = Self::create_token(user, pool).await?;
+        pool.execute(
+            query(
+                "INSERT INTO token_storage (user_id, token_hash, refresh_token_hash, refresh_expires_at, \
+                 user_agent, ip, device_name) VALUES($1, $2, $3, now() + make_interval(secs => $4), $5, $6, \
+                 $7)",
+            )
+            .bind(user.id().to_string())
+            .bind(hash(&token))
+            .bind(hash(&refresh_token))
+            .bind(lifetimes.refresh.as_secs_f64())
+            .bind(&session.user_agent)
+            .bind(&session.ip)
+            .bind(&session.device_name),
+        )
+        .await?;
+
+        Ok(TokenPair {
+            access_token: Self::encrypt(Self::claims(user, &token, lifetimes.access)?, keys)?,
+            refresh_token,
+        })
+    }
 
-        let mut claims = Claims::new()?;
-        claims.non_expiring();
-        claims.add_additional("user_id", user.id())?;
+    /// Exchanges refresh token for a new pair. Both tokens of the session are
+    /// rotated so the old access and refresh tokens stop working.
+    pub async fn refresh<User: SercliUser>(
+        refresh_token: &str,
+        lifetimes: TokenLifetimes,
+        keys: &TokenKeys,
+        pool: &PgPool,
+    ) -> Result<TokenPair> {
+        let token: String = 32.fake();
+        let new_refresh_token: String = 64.fake();
+
+        let user_id: Option<String> = query_scalar(
+            "UPDATE token_storage SET token_hash = $1, refresh_token_hash = $2, refresh_expires_at = now() \
+             + make_interval(secs => $3), last_used_at = now() WHERE refresh_token_hash = $4 AND \
+             refresh_expires_at > now() RETURNING user_id",
+        )
+        .bind(hash(&token))
+        .bind(hash(&new_refresh_token))
+        .bind(lifetimes.refresh.as_secs_f64())
+        .bind(hash(refresh_token))
+        .fetch_optional(pool)
+        .await?;
+
+        let Some(user_id) = user_id else {
+            bail!("This refresh token is not valid anymore")
+        };
+
+        let user = User::with_id(User::primary_key().parse(&user_id)?, pool).await?;
+        let claims = Self::claims(&user, &token, lifetimes.access)?;
+
+        Ok(TokenPair {
+            access_token:  Self::encrypt(claims, keys)?,
+            refresh_token: new_refresh_token,
+        })
+    }
+
+    fn claims(user: &impl SercliUser, token: &str, lifetime: Duration) -> Result<Claims> {
+        let mut claims = Claims::new_expires_in(&lifetime)?;
+        claims.add_additional("user_id", user.id().to_string())?;
         claims.add_additional("user_login", user.login())?;
         claims.add_additional("user_token", token)?;
-
-        Ok(local::encrypt(&key, &claims, None, None)?)
+        Ok(claims)
     }
 
-    pub async fn check_token<User: SercliUser>(token: &str, pool: &PgPool) -> Result<User> {
-        Self::create_table(pool).await?;
+    /// Encrypts with active key and puts its id to `kid` footer claim
+    fn encrypt(claims: Claims, keys: &TokenKeys) -> Result<String> {
+        let keys = keys.keys();
+        let key = &keys[0];
+
+        let mut footer = Footer::new();
+        footer.key_id(&Id::from(&key.key));
 
-        let key = Self::get_encryption_key(pool).await?;
+        Ok(local::encrypt(&key.key, &claims, Some(&footer), None)?)
+    }
 
-        let mut validation_rules = ClaimsValidationRules::new();
-        validation_rules.allow_non_expiring();
+    /// Returns user of the token and id of its session
+    pub async fn check_token<User: SercliUser>(
+        token: &str,
+        keys: &TokenKeys,
+        pool: &PgPool,
+    ) -> Result<(User, ID)> {
+        // Rejects expired tokens and tokens issued without expiration
+        let validation_rules = ClaimsValidationRules::new();
+
+        let untrusted_token = UntrustedToken::<Local, V4>::try_from(token)?;
+
+        let trusted_token = if untrusted_token.untrusted_footer().is_empty() {
+            // Tokens issued before key rotation have no key id
+            keys.keys()
+                .iter()
+                .find_map(|key| {
+                    local::decrypt(&key.key, &untrusted_token, &validation_rules, None, None).ok()
+                })
+                .ok_or_else(|| anyhow!("Token is not encrypted with any of the keys"))?
+        } else {
+            let mut footer = Footer::new();
+            footer.parse_bytes(untrusted_token.untrusted_footer())?;
+
+            let key_id = footer
+                .get_claim("kid")
+                .and_then(|kid| kid.as_str())
+                .ok_or_else(|| anyhow!("No kid in token footer"))?;
+
+            // Key could be rotated by another server
+            if !keys.contains(key_id) {
+                keys.refresh(pool).await?;
+            }
+
+            let keys = keys.keys();
+
+            let Some(key) = keys.iter().find(|key| key.id == key_id) else {
+                bail!("Token key {key_id} is retired")
+            };
+
+            local::decrypt(&key.key, &untrusted_token, &validation_rules, Some(&footer), None)?
+        };
 
-        let untrusted_token = UntrustedToken::<Local, V4>::try_from(&token.to_string())?;
-        let trusted_token = local::decrypt(&key, &untrusted_token, &validation_rules, None, None)?;
         let claims = trusted_token.payload_claims().ok_or_else(|| anyhow!("No claims"))?;
 
-        let user_id: ID = claims
-            .get_claim("user_id")
-            .ok_or_else(|| anyhow!("No user_id in claim"))?
-            .as_i64()
-            .ok_or_else(|| anyhow!("Invalid value in user_id"))?
-            .try_into()?;
+        let user_id = claims.get_claim("user_id").ok_or_else(|| anyhow!("No user_id in claim"))?;
+
+        // Tokens issued before string ids have user_id as number
+        let user_id: EntityId = match user_id.as_i64() {
+            Some(id) => EntityId::Int(id.try_into()?),
+            None => User::primary_key()
+                .parse(user_id.as_str().ok_or_else(|| anyhow!("Invalid value in user_id"))?)?,
+        };
 
         let user_login: &str = claims
             .get_claim("user_login")
@@ -74,10 +266,12 @@ impl AccessToken {
             bail!("Invalid user login in claim")
         }
 
-        let token: Option<UserToken> = query_as("SELECT * FROM token_storage WHERE token = $1")
-            .bind(user_token)
-            .fetch_optional(pool)
-            .await?;
+        let token: Option<UserToken> = query_as(
+            "UPDATE token_storage SET last_used_at = now() WHERE token_hash = $1 RETURNING id, user_id",
+        )
+        .bind(hash(user_token))
+        .fetch_optional(pool)
+        .await?;
 
         let Some(token) = token else {
             bail!("This token is not valid anymore")
@@ -85,77 +279,158 @@ impl AccessToken {
 
         // This is a security incident. It means that encryption key has leaked.
         // Think of a way to notify about this
-        if token.user_id != user.id() {
+        if token.user_id != user.id().to_string() {
             error!("This is bad");
             bail!("Invalid user id in token");
         }
 
-        Ok(user)
+        Ok((user, token.id))
     }
 
-    #[allow(dead_code)]
-    pub async fn invalidate_all_tokens<User: SercliUser>(user: &User, pool: &PgPool) -> Result<()> {
-        pool.execute(query("DELETE FROM token_storage WHERE user_id = $1").bind(user.id()))
-            .await?;
-
-        Ok(())
+    /// Sessions of `user_id` with valid refresh tokens
+    pub async fn sessions(user_id: &EntityId, current: ID, pool: &PgPool) -> Result<Vec<Session>> {
+        Ok(query_as(
+            "SELECT id, created_at, last_used_at, user_agent, ip, device_name, id = $2 AS current FROM \
+             token_storage WHERE user_id = $1 AND refresh_expires_at > now() ORDER BY \
+             COALESCE(last_used_at, created_at) DESC",
+        )
+        .bind(user_id.to_string())
+        .bind(current)
+        .fetch_all(pool)
+        .await?)
     }
 
-    async fn get_encryption_key(pool: &PgPool) -> Result<SymmetricKey<V4>> {
-        use pasetors::keys::Generate;
-
-        const STORAGE_KEY: &str = "access_token_encryption_key";
+    pub async fn revoke_session(user_id: &EntityId, session: ID, pool: &PgPool) -> Result<()> {
+        let result = pool
+            .execute(
+                query("DELETE FROM token_storage WHERE id = $1 AND user_id = $2")
+                    .bind(session)
+                    .bind(user_id.to_string()),
+            )
+            .await?;
 
-        if let Some(data) = DBStorage::get(STORAGE_KEY, pool).await? {
-            return Ok(SymmetricKey::<V4>::from(&data)?);
+        if result.rows_affected() == 0 {
+            bail!("No session with id {session}")
         }
 
-        let key = SymmetricKey::<V4>::generate()?;
-
-        DBStorage::set(STORAGE_KEY, key.as_bytes(), pool).await?;
-
-        Ok(key)
+        Ok(())
     }
 
-    async fn create_token<User: SercliUser>(user: &User, pool: &PgPool) -> Result<String> {
-        let token = UserToken {
-            user_id: user.id(),
-            token:   32.fake(),
-        };
-
+    pub async fn name_session(session: ID, name: &str, pool: &PgPool) -> Result<()> {
         pool.execute(
-            query("INSERT INTO token_storage (user_id, token) VALUES($1, $2)")
-                .bind(token.user_id)
-                .bind(&token.token),
+            query("UPDATE token_storage SET device_name = $1 WHERE id = $2")
+                .bind(name)
+                .bind(session),
         )
         .await?;
 
-        Ok(token.token)
+        Ok(())
     }
 
-    async fn create_table(pool: &PgPool) -> Result<()> {
-        pool.execute(query(
+    #[allow(dead_code)]
+    pub async fn invalidate_all_tokens<User: SercliUser>(user: &User, pool: &PgPool) -> Result<()> {
+        pool.execute(query("DELETE FROM token_storage WHERE user_id = $1").bind(user.id().to_string()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Creates `token_storage` and migrates tables of older versions. Runs
+    /// once on server start so requests only query the table.
+    pub(crate) async fn create_table(pool: &PgPool) -> Result<()> {
+        let mut tx = pool.begin().await?;
+
+        // Servers starting at the same time wait for the first one to migrate
+        // the table instead of renaming columns and creating indexes twice
+        tx.execute(query("SELECT pg_advisory_xact_lock(hashtext('token_storage'))"))
+            .await?;
+
+        for statement in [
             r"CREATE TABLE IF NOT EXISTS token_storage (
-                   id SERIAL       PRIMARY KEY,
-              user_id INTEGER      NOT NULL,
-                token VARCHAR(255) NOT NULL
+                   id SERIAL    PRIMARY KEY,
+              user_id TEXT      NOT NULL,
+           token_hash TEXT      NOT NULL,
+   refresh_token_hash TEXT,
+   refresh_expires_at TIMESTAMP,
+           created_at TIMESTAMP NOT NULL DEFAULT now(),
+         last_used_at TIMESTAMP,
+           user_agent TEXT,
+                   ip TEXT,
+          device_name TEXT
 );",
-        ))
-        .await
-        .map_err(|e| anyhow!(e))?;
+            // Tables created before user ids of any type were supported
+            r"DO $$
+BEGIN
+    IF EXISTS (SELECT FROM information_schema.columns WHERE table_schema = current_schema()
+               AND table_name = 'token_storage' AND column_name = 'user_id' AND data_type <> 'text') THEN
+        ALTER TABLE token_storage ALTER COLUMN user_id TYPE TEXT USING user_id::TEXT;
+    END IF;
+END $$;",
+            // Tables created before tokens were hashed store them in plain text
+            r"DO $$
+BEGIN
+    IF EXISTS (SELECT FROM information_schema.columns WHERE table_schema = current_schema()
+               AND table_name = 'token_storage' AND column_name = 'token') THEN
+        ALTER TABLE token_storage RENAME COLUMN token TO token_hash;
+        ALTER TABLE token_storage ALTER COLUMN token_hash TYPE TEXT;
+        UPDATE token_storage SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
+    END IF;
+    IF EXISTS (SELECT FROM information_schema.columns WHERE table_schema = current_schema()
+               AND table_name = 'token_storage' AND column_name = 'refresh_token') THEN
+        ALTER TABLE token_storage RENAME COLUMN refresh_token TO refresh_token_hash;
+        ALTER TABLE token_storage ALTER COLUMN refresh_token_hash TYPE TEXT;
+        UPDATE token_storage SET refresh_token_hash = encode(sha256(convert_to(refresh_token_hash, 'UTF8')), 'hex')
+            WHERE refresh_token_hash IS NOT NULL;
+    END IF;
+END $$;",
+            // Tables created before refresh tokens were supported
+            "ALTER TABLE token_storage ADD COLUMN IF NOT EXISTS refresh_token_hash TEXT, ADD COLUMN IF NOT \
+             EXISTS refresh_expires_at TIMESTAMP;",
+            // Tables created before sessions had metadata
+            "ALTER TABLE token_storage ADD COLUMN IF NOT EXISTS created_at TIMESTAMP NOT NULL DEFAULT \
+             now(), ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMP, ADD COLUMN IF NOT EXISTS user_agent \
+             TEXT, ADD COLUMN IF NOT EXISTS ip TEXT, ADD COLUMN IF NOT EXISTS device_name TEXT;",
+            "CREATE INDEX IF NOT EXISTS token_storage_token_hash_idx ON token_storage (token_hash);",
+            "CREATE INDEX IF NOT EXISTS token_storage_refresh_token_hash_idx ON token_storage \
+             (refresh_token_hash);",
+        ] {
+            tx.execute(query(statement)).await.map_err(|e| anyhow!(e))?;
+        }
+
+        tx.commit().await?;
 
         Ok(())
     }
 }
 
+/// Only hashes of tokens are stored so database contents are not enough to
+/// use a session. Hex encoded SHA-256, same as
+/// `encode(sha256(convert_to(token, 'UTF8')), 'hex')` in Postgres.
+fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token))
+}
+
 #[cfg(test)]
 mod test {
+    use std::{
+        net::{IpAddr, SocketAddr},
+        time::Duration,
+    };
+
     use anyhow::Result;
+    use axum::{extract::ConnectInfo, http::Request};
     use fake::{Fake, faker::internet::en::SafeEmail};
-    use reflected::Reflected;
-    use sqlx::FromRow;
-
-    use crate::{Crud, ID, SercliUser, db::prepare_db, server::access_token::AccessToken};
+    use reflected::{Field, Reflected};
+    use sqlx::{FromRow, query_scalar};
+
+    use crate::{
+        Crud, Entity, EntityId, ID, PrimaryKey, SercliUser, Uuid,
+        db::prepare_db,
+        server::{
+            KeySource, TokenKeys,
+            access_token::{AccessToken, SessionInfo, TokenLifetimes, TrustedProxies, hash},
+        },
+    };
 
     #[derive(Debug, Default, Clone, Reflected, FromRow)]
     struct SomeUser {
@@ -163,9 +438,11 @@ mod test {
         email: String,
     }
 
+    impl Entity for SomeUser {}
+
     impl SercliUser for SomeUser {
-        fn id(&self) -> ID {
-            self.id
+        fn id(&self) -> EntityId {
+            self.id.into()
         }
 
         fn password(&self) -> &str {
@@ -185,6 +462,10 @@ mod test {
     async fn generate_token() -> Result<()> {
         let pool = prepare_db().await?;
 
+        AccessToken::create_table(&pool).await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+
         let user = SomeUser {
             id:    0,
             email: SafeEmail().fake(),
@@ -196,15 +477,23 @@ mod test {
 
         AccessToken::invalidate_all_tokens(&user, &pool).await?;
 
-        let token = AccessToken::generate_token(&user, &pool).await?;
+        let token = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?
+        .access_token;
 
-        let authorized_user: SomeUser = AccessToken::check_token(&token, &pool).await?;
+        let (authorized_user, _): (SomeUser, _) = AccessToken::check_token(&token, &keys, &pool).await?;
 
         assert_eq!(user.email, authorized_user.email);
 
         AccessToken::invalidate_all_tokens(&user, &pool).await?;
 
-        let error = AccessToken::check_token::<SomeUser>(&token, &pool)
+        let error = AccessToken::check_token::<SomeUser>(&token, &keys, &pool)
             .await
             .expect_err("No error on invalidated token");
 
@@ -212,4 +501,426 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn refresh_token() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+        SomeUser::create_table(&pool).await?;
+
+        let user = SomeUser {
+            id:    0,
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let lifetimes = TokenLifetimes::default();
+
+        let pair =
+            AccessToken::generate_tokens(&user, &SessionInfo::default(), lifetimes, &keys, &pool).await?;
+        let refreshed =
+            AccessToken::refresh::<SomeUser>(&pair.refresh_token, lifetimes, &keys, &pool).await?;
+
+        let (authorized_user, _): (SomeUser, _) =
+            AccessToken::check_token(&refreshed.access_token, &keys, &pool).await?;
+        assert_eq!(user.email, authorized_user.email);
+
+        assert!(
+            AccessToken::check_token::<SomeUser>(&pair.access_token, &keys, &pool)
+                .await
+                .is_err()
+        );
+
+        let error = AccessToken::refresh::<SomeUser>(&pair.refresh_token, lifetimes, &keys, &pool)
+            .await
+            .expect_err("Refresh token was reused");
+        assert!(format!("{error}").contains("This refresh token is not valid anymore"));
+
+        AccessToken::refresh::<SomeUser>(&refreshed.refresh_token, lifetimes, &keys, &pool).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tokens_are_hashed() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+        SomeUser::create_table(&pool).await?;
+
+        let user = SomeUser {
+            id:    0,
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let pair = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?;
+
+        let stored = |hash: String| {
+            query_scalar::<_, i64>("SELECT COUNT(*) FROM token_storage WHERE refresh_token_hash = $1")
+                .bind(hash)
+                .fetch_one(&pool)
+        };
+
+        assert_eq!(stored(pair.refresh_token.clone()).await?, 0);
+        assert_eq!(stored(hash(&pair.refresh_token)).await?, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_expiration() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+        SomeUser::create_table(&pool).await?;
+
+        let user = SomeUser {
+            id:    0,
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let lifetimes = TokenLifetimes {
+            access:  Duration::from_secs(1),
+            refresh: Duration::from_secs(1),
+        };
+
+        let pair =
+            AccessToken::generate_tokens(&user, &SessionInfo::default(), lifetimes, &keys, &pool).await?;
+
+        AccessToken::check_token::<SomeUser>(&pair.access_token, &keys, &pool).await?;
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+
+        assert!(
+            AccessToken::check_token::<SomeUser>(&pair.access_token, &keys, &pool)
+                .await
+                .is_err()
+        );
+        assert!(
+            AccessToken::refresh::<SomeUser>(&pair.refresh_token, lifetimes, &keys, &pool)
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sessions() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+        UuidUser::create_table(&pool).await?;
+
+        let user = UuidUser {
+            id:    Uuid::default(),
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+        let lifetimes = TokenLifetimes::default();
+
+        let phone = SessionInfo {
+            user_agent:  Some("Phone".to_string()),
+            ip:          Some("10.0.0.1".to_string()),
+            device_name: Some("Peter's phone".to_string()),
+        };
+
+        let phone_tokens = AccessToken::generate_tokens(&user, &phone, lifetimes, &keys, &pool).await?;
+        let laptop_tokens =
+            AccessToken::generate_tokens(&user, &SessionInfo::default(), lifetimes, &keys, &pool).await?;
+
+        let (_, laptop) =
+            AccessToken::check_token::<UuidUser>(&laptop_tokens.access_token, &keys, &pool).await?;
+
+        AccessToken::name_session(laptop, "Laptop", &pool).await?;
+
+        let sessions = AccessToken::sessions(&user.id(), laptop, &pool).await?;
+
+        assert_eq!(sessions.len(), 2);
+
+        assert_eq!(sessions[0].id, laptop);
+        assert!(sessions[0].current);
+        assert!(sessions[0].last_used_at.is_some());
+        assert_eq!(sessions[0].device_name.as_deref(), Some("Laptop"));
+
+        assert!(!sessions[1].current);
+        assert_eq!(sessions[1].last_used_at, None);
+        assert_eq!(sessions[1].user_agent.as_deref(), Some("Phone"));
+        assert_eq!(sessions[1].ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(sessions[1].device_name.as_deref(), Some("Peter's phone"));
+
+        let other_user = UuidUser {
+            id:    Uuid::default(),
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        assert!(
+            AccessToken::revoke_session(&other_user.id(), sessions[1].id, &pool)
+                .await
+                .is_err()
+        );
+
+        AccessToken::revoke_session(&user.id(), sessions[1].id, &pool).await?;
+
+        assert!(
+            AccessToken::check_token::<UuidUser>(&phone_tokens.access_token, &keys, &pool)
+                .await
+                .is_err()
+        );
+        AccessToken::check_token::<UuidUser>(&laptop_tokens.access_token, &keys, &pool).await?;
+
+        assert_eq!(AccessToken::sessions(&user.id(), laptop, &pool).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn session_ip() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let ip = |connected: &str, forwarded_for: &str, proxies: Vec<IpAddr>| {
+            let (parts, ()) = Request::builder()
+                .header("x-forwarded-for", forwarded_for)
+                .extension(ConnectInfo(SocketAddr::new(connected.parse().unwrap(), 8000)))
+                .extension(TrustedProxies(proxies))
+                .body(())
+                .unwrap()
+                .into_parts();
+
+            SessionInfo::from_parts(&parts).ip.unwrap()
+        };
+
+        assert_eq!(ip("1.1.1.1", "2.2.2.2", vec![]), "1.1.1.1");
+        assert_eq!(ip("1.1.1.1", "2.2.2.2", vec![proxy]), "1.1.1.1");
+        assert_eq!(ip("10.0.0.1", "2.2.2.2", vec![proxy]), "2.2.2.2");
+        assert_eq!(ip("10.0.0.1", "6.6.6.6, 2.2.2.2", vec![proxy]), "2.2.2.2");
+        assert_eq!(ip("10.0.0.1", "2.2.2.2, 10.0.0.1", vec![proxy]), "2.2.2.2");
+    }
+
+    #[tokio::test]
+    async fn key_rotation() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+        SomeUser::create_table(&pool).await?;
+
+        let user = SomeUser {
+            id:    0,
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+
+        // Server started before the rotation
+        let other = TokenKeys::load(KeySource::Database, &pool).await?;
+
+        let old = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?;
+
+        let key_id = keys.rotate(&pool).await?;
+
+        assert_eq!(keys.key_ids()[0], key_id);
+        assert!(!other.key_ids().contains(&key_id));
+
+        AccessToken::check_token::<SomeUser>(&old.access_token, &keys, &pool).await?;
+
+        let new = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?;
+        AccessToken::check_token::<SomeUser>(&new.access_token, &keys, &pool).await?;
+
+        // Rotated key is loaded by token encrypted with it
+        AccessToken::check_token::<SomeUser>(&new.access_token, &other, &pool).await?;
+        assert!(other.key_ids().contains(&key_id));
+
+        let error = keys.retire(&key_id, &pool).await.expect_err("Active key was retired");
+        assert!(format!("{error}").contains("can't be retired"));
+
+        assert!(keys.retire("k4.lid.unknown", &pool).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn file_keys() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+        SomeUser::create_table(&pool).await?;
+
+        let user = SomeUser {
+            id:    0,
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let old_key = TokenKeys::generate()?;
+        let new_key = TokenKeys::generate()?;
+
+        let path = std::env::temp_dir().join(format!("sercli_keys_{}", Uuid::new_v4()));
+
+        std::fs::write(&path, &old_key)?;
+
+        let keys = TokenKeys::load(KeySource::File(path.clone()), &pool).await?;
+
+        assert!(keys.rotate(&pool).await.is_err());
+
+        let old = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?;
+
+        let database_keys = TokenKeys::load(KeySource::Database, &pool).await?;
+
+        assert!(
+            AccessToken::check_token::<SomeUser>(&old.access_token, &database_keys, &pool)
+                .await
+                .is_err()
+        );
+
+        std::fs::write(&path, format!("{new_key}\n{old_key}\n"))?;
+        keys.refresh(&pool).await?;
+
+        let new = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?;
+
+        AccessToken::check_token::<SomeUser>(&old.access_token, &keys, &pool).await?;
+        AccessToken::check_token::<SomeUser>(&new.access_token, &keys, &pool).await?;
+
+        std::fs::write(&path, &new_key)?;
+        keys.refresh(&pool).await?;
+
+        let error = AccessToken::check_token::<SomeUser>(&old.access_token, &keys, &pool)
+            .await
+            .expect_err("Token of retired key is valid");
+        assert!(format!("{error}").contains("is retired"));
+
+        AccessToken::check_token::<SomeUser>(&new.access_token, &keys, &pool).await?;
+
+        std::fs::remove_file(&path)?;
+
+        assert!(keys.refresh(&pool).await.is_err());
+        assert!(TokenKeys::load(KeySource::File(path), &pool).await.is_err());
+
+        Ok(())
+    }
+
+    #[derive(Debug, Default, Clone, Reflected, FromRow)]
+    struct UuidUser {
+        id:    Uuid,
+        email: String,
+    }
+
+    impl Entity for UuidUser {
+        fn primary_key() -> PrimaryKey {
+            PrimaryKey::Uuid
+        }
+
+        fn uuid_value(&self, field: Field<Self>) -> Option<Uuid> {
+            (field == Self::ID).then_some(self.id)
+        }
+    }
+
+    impl SercliUser for UuidUser {
+        fn id(&self) -> EntityId {
+            self.id.into()
+        }
+
+        fn password(&self) -> &str {
+            ""
+        }
+
+        fn login(&self) -> &str {
+            &self.email
+        }
+
+        fn login_field_name() -> &'static str {
+            "email"
+        }
+    }
+
+    #[tokio::test]
+    async fn uuid_user_token() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        AccessToken::create_table(&pool).await?;
+
+        let keys = TokenKeys::load(KeySource::Database, &pool).await?;
+        UuidUser::create_table(&pool).await?;
+
+        let user = UuidUser {
+            id:    Uuid::default(),
+            email: SafeEmail().fake(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let token = AccessToken::generate_tokens(
+            &user,
+            &SessionInfo::default(),
+            TokenLifetimes::default(),
+            &keys,
+            &pool,
+        )
+        .await?
+        .access_token;
+
+        let (authorized_user, _): (UuidUser, _) = AccessToken::check_token(&token, &keys, &pool).await?;
+
+        assert_eq!(user.id, authorized_user.id);
+
+        AccessToken::invalidate_all_tokens(&user, &pool).await?;
+
+        assert!(AccessToken::check_token::<UuidUser>(&token, &keys, &pool).await.is_err());
+
+        Ok(())
+    }
 }
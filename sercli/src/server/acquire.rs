@@ -0,0 +1,39 @@
+use std::ops::DerefMut;
+
+use sqlx::{PgConnection, PgPool, Postgres, Transaction, pool::PoolConnection};
+
+/// Anything that can provide Postgres connection: `&PgPool`,
+/// `&mut PgConnection` or `&mut Transaction`.
+///
+/// `sqlx::Acquire` is not used directly because its lifetime parameter
+/// breaks `Send` inference for futures of async trait methods
+/// (see <https://github.com/rust-lang/rust/issues/100013>).
+pub trait PgAcquire: Send {
+    type Connection: DerefMut<Target = PgConnection> + Send;
+
+    fn acquire(self) -> impl Future<Output = sqlx::Result<Self::Connection>> + Send;
+}
+
+impl PgAcquire for &PgPool {
+    type Connection = PoolConnection<Postgres>;
+
+    fn acquire(self) -> impl Future<Output = sqlx::Result<Self::Connection>> + Send {
+        PgPool::acquire(self)
+    }
+}
+
+impl<'c> PgAcquire for &'c mut PgConnection {
+    type Connection = &'c mut PgConnection;
+
+    async fn acquire(self) -> sqlx::Result<Self::Connection> {
+        Ok(self)
+    }
+}
+
+impl<'c> PgAcquire for &'c mut Transaction<'_, Postgres> {
+    type Connection = &'c mut PgConnection;
+
+    async fn acquire(self) -> sqlx::Result<Self::Connection> {
+        Ok(&mut **self)
+    }
+}
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use axum::{
     extract::{FromRef, FromRequestParts},
     http::request::Parts,
@@ -8,18 +8,30 @@ use axum::{
 use sqlx::PgPool;
 
 use crate::{
-    SercliUser,
-    server::{AppError, access_token::AccessToken},
+    SercliUser, TokenPair,
+    server::{
+        AppError, TokenKeys,
+        access_token::{AccessToken, SessionInfo, TokenLifetimes},
+    },
 };
 
 pub struct AuthorizeRequest<User: SercliUser> {
-    pool: PgPool,
-    _p:   PhantomData<User>,
+    pool:      PgPool,
+    lifetimes: TokenLifetimes,
+    keys:      TokenKeys,
+    session:   SessionInfo,
+    _p:        PhantomData<User>,
 }
 
 impl<User: SercliUser> AuthorizeRequest<User> {
+    /// Access token of a new session. Use `generate_tokens` if the client
+    /// should be able to refresh it.
     pub async fn generate_token(&self, user: &User) -> Result<String> {
-        AccessToken::generate_token(user, &self.pool).await
+        Ok(self.generate_tokens(user).await?.access_token)
+    }
+
+    pub async fn generate_tokens(&self, user: &User) -> Result<TokenPair> {
+        AccessToken::generate_tokens(user, &self.session, self.lifetimes, &self.keys, &self.pool).await
     }
 }
 
@@ -30,11 +42,18 @@ where
 {
     type Rejection = AppError;
 
-    async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let pool = PgPool::from_ref(state);
 
         Ok(Self {
             pool,
+            lifetimes: parts.extensions.get().copied().unwrap_or_default(),
+            keys: parts
+                .extensions
+                .get()
+                .cloned()
+                .ok_or_else(|| anyhow!("No TokenKeys extension. It is added by Server on start."))?,
+            session: SessionInfo::from_parts(parts),
             _p: PhantomData,
         })
     }
@@ -9,27 +9,49 @@ use derive_more::{Deref, DerefMut, From};
 use sqlx::{PgPool, query};
 
 use crate::{
-    SercliUser,
-    server::{AppError, access_token::AccessToken},
+    ID, SercliUser, Session,
+    server::{AppError, TokenKeys, access_token::AccessToken},
 };
 
 #[derive(Deref, DerefMut, From)]
 pub struct AuthorizedUser<User: SercliUser> {
     #[deref]
     #[deref_mut]
-    user: User,
-    pool: PgPool,
+    user:    User,
+    pool:    PgPool,
+    /// Session of the token used in this request
+    session: ID,
 }
 
 impl<User: SercliUser> AuthorizedUser<User> {
     pub async fn revoke_all_tokens(&self) -> Result<()> {
         query("DELETE FROM token_storage WHERE user_id = $1")
-            .bind(self.user.id())
+            .bind(self.user.id().to_string())
             .execute(&self.pool)
             .await?;
 
         Ok(())
     }
+
+    /// Active sessions of the user. Current one has `Session::current` set.
+    pub async fn sessions(&self) -> Result<Vec<Session>> {
+        AccessToken::sessions(&self.user.id(), self.session, &self.pool).await
+    }
+
+    /// Signs out one of the sessions of the user
+    pub async fn revoke_session(&self, session: ID) -> Result<()> {
+        AccessToken::revoke_session(&self.user.id(), session, &self.pool).await
+    }
+
+    /// Signs out session of this request
+    pub async fn revoke_current_session(&self) -> Result<()> {
+        self.revoke_session(self.session).await
+    }
+
+    /// Sets `Session::device_name` of the current session
+    pub async fn name_session(&self, name: &str) -> Result<()> {
+        AccessToken::name_session(self.session, name, &self.pool).await
+    }
 }
 
 impl<S: Sync, User: SercliUser + Debug> FromRequestParts<S> for AuthorizedUser<User>
@@ -48,9 +70,13 @@ where
 
         let token = token.to_str()?;
 
-        Ok(Self {
-            user: AccessToken::check_token(token, &pool).await?,
-            pool,
-        })
+        let keys: &TokenKeys = parts
+            .extensions
+            .get()
+            .ok_or_else(|| anyhow!("No TokenKeys extension. It is added by Server on start."))?;
+
+        let (user, session) = AccessToken::check_token(token, keys, &pool).await?;
+
+        Ok(Self { user, pool, session })
     }
 }
@@ -0,0 +1,59 @@
+use std::fmt::{Display, Formatter};
+
+use derive_more::{Deref, DerefMut, From};
+use reflected::ToReflectedVal;
+use serde::{Deserialize, Serialize};
+
+/// `Vec<u8>` usable as `Reflected` entity field. Stored as `BYTEA`.
+/// Other `Vec` fields are not supported.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    Deref,
+    DerefMut,
+    From,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Bytes(Vec<u8>);
+
+impl Bytes {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Hex string
+impl Display for Bytes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl ToReflectedVal<Bytes> for &str {
+    fn to_reflected_val(&self) -> Result<Bytes, String> {
+        if self.len() % 2 != 0 {
+            return Err(format!("Odd length of hex string {self}"));
+        }
+
+        (0..self.len())
+            .step_by(2)
+            .map(|i| {
+                self.get(i..i + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| format!("Failed to parse {self} as hex"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Bytes)
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt::Write;
+
+use reflected::Field;
+
+use crate::Entity;
+
+/// Constraints of entity column used by `Entity::create_table_query`.
+///
+/// ```ignore
+/// impl Entity for User {
+///     fn columns() -> Vec<Column<Self>> {
+///         vec![
+///             Column::new(User::EMAIL).unique().varchar(255),
+///             Column::new(User::AGE).index().default("18").check("age >= 0"),
+///         ]
+///     }
+/// }
+///
+/// impl Entity for Wallet {
+///     fn columns() -> Vec<Column<Self>> {
+///         vec![Column::new(Wallet::USER_ID).references::<User>().index()]
+///     }
+/// }
+/// ```
+pub struct Column<T> {
+    pub(crate) field:      Field<T>,
+    pub(crate) unique:     bool,
+    pub(crate) index:      bool,
+    pub(crate) default:    Option<String>,
+    pub(crate) check:      Option<String>,
+    pub(crate) varchar:    Option<u32>,
+    pub(crate) references: Option<String>,
+}
+
+impl<T> Column<T> {
+    pub fn new(field: Field<T>) -> Self {
+        Self {
+            field,
+            unique: false,
+            index: false,
+            default: None,
+            check: None,
+            varchar: None,
+            references: None,
+        }
+    }
+
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    /// Creates `{table}_{column}_idx` index on the column
+    pub fn index(mut self) -> Self {
+        self.index = true;
+        self
+    }
+
+    /// SQL expression used as is: `.default("'guest'")`, `.default("now()")`
+    pub fn default(mut self, expression: impl ToString) -> Self {
+        self.default = Some(expression.to_string());
+        self
+    }
+
+    /// SQL boolean expression used as is: `.check("age >= 0")`
+    pub fn check(mut self, expression: impl ToString) -> Self {
+        self.check = Some(expression.to_string());
+        self
+    }
+
+    /// Stores text column as `VARCHAR(length)` instead of `TEXT`
+    pub fn varchar(mut self, length: u32) -> Self {
+        self.varchar = Some(length);
+        self
+    }
+
+    /// Foreign key to primary key of `R` table
+    pub fn references<R: Entity>(mut self) -> Self {
+        self.references = Some(format!("{} ({})", R::qualified_table_name(), R::id_column()));
+        self
+    }
+
+    pub(crate) fn constraints_sql(&self) -> String {
+        let mut sql = String::new();
+
+        if self.unique {
+            sql.push_str(" UNIQUE");
+        }
+
+        if let Some(default) = &self.default {
+            write!(sql, " DEFAULT {default}").unwrap();
+        }
+
+        if let Some(check) = &self.check {
+            write!(sql, " CHECK ({check})").unwrap();
+        }
+
+        if let Some(references) = &self.references {
+            write!(sql, " REFERENCES {references}").unwrap();
+        }
+
+        sql
+    }
+}
@@ -0,0 +1,22 @@
+use std::fmt::{Display, Formatter};
+
+/// Versioned entity was changed by someone else since it was read.
+/// Returned by updates wrapped in `anyhow::Error`:
+/// `error.downcast_ref::<ConcurrencyError>()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConcurrencyError {
+    pub entity: &'static str,
+    pub id:     String,
+}
+
+impl Display for ConcurrencyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} with id {} was modified or deleted since it was read",
+            self.entity, self.id
+        )
+    }
+}
+
+impl std::error::Error for ConcurrencyError {}
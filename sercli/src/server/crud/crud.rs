@@ -1,77 +1,395 @@
-use anyhow::Result;
-use sqlx::{Executor, PgPool, Postgres, query};
+use std::collections::HashMap;
 
-use crate::{Entity, ID, server::crud::CrudRequest};
+use anyhow::{Result, bail};
+use reflected::Field;
+use sqlx::{Executor, Postgres, query};
+
+use crate::{
+    Entity,
+    server::{
+        PgAcquire,
+        crud::{
+            ConcurrencyError, CrudRequest, EntityId, EntitySchema, Order, id_value, managed_updates,
+            verify_schemas,
+        },
+    },
+};
 
 #[allow(async_fn_in_trait)]
 pub trait Crud: Sized + Entity {
-    async fn create_table(pool: &PgPool) -> Result<()>;
-    async fn drop_table(pool: &PgPool) -> Result<()>;
+    async fn create_table(db: impl PgAcquire) -> Result<()>;
+    async fn drop_table(db: impl PgAcquire) -> Result<()>;
+
+    async fn insert(self, db: impl PgAcquire) -> Result<Self>;
+
+    /// Inserts entities with multi row `VALUES` list.
+    /// Split into several queries to fit Postgres limit of bind parameters per
+    /// query. Use transaction if all chunks must be inserted atomically.
+    async fn insert_many(entities: Vec<Self>, db: impl PgAcquire) -> Result<Vec<Self>>;
+
+    async fn upsert(self, conflict_fields: &[Field<Self>], db: impl PgAcquire) -> Result<Self>;
+    async fn get_all(db: impl PgAcquire) -> Result<Vec<Self>>;
+    async fn with_id(id: impl Into<EntityId>, db: impl PgAcquire) -> Result<Self>;
+
+    /// Entity with primary key values in order of `Entity::key` fields.
+    /// Works with `id` entities as well: `Wallet::find(&[id.into()], db)`
+    async fn find(key: &[EntityId], db: impl PgAcquire) -> Result<Option<Self>>;
+
+    /// Deletes entity with primary key values in order of `Entity::key` fields
+    async fn delete_by_key(key: &[EntityId], db: impl PgAcquire) -> Result<()>;
+    /// Sets `deleted_at` of soft delete entities and deletes the row otherwise
+    async fn delete(self, db: impl PgAcquire) -> Result<()>;
+
+    /// Deletes the row even if entity uses soft delete
+    async fn force_delete(self, db: impl PgAcquire) -> Result<()>;
+
+    /// Clears `deleted_at` of soft deleted entity
+    async fn restore(self, db: impl PgAcquire) -> Result<Self>;
 
-    async fn insert(self, pool: &PgPool) -> Result<Self>;
-    async fn get_all(pool: &PgPool) -> Result<Vec<Self>>;
-    async fn with_id(id: i32, pool: &PgPool) -> Result<Self>;
-    async fn delete(self, pool: &PgPool) -> Result<()>;
+    async fn update(&self, db: impl PgAcquire) -> Result<Self>
+    where Self: Clone;
+    async fn update_fields(&self, fields: &[Field<Self>], db: impl PgAcquire) -> Result<Self>
+    where Self: Clone;
 
-    fn get(pool: &PgPool) -> CrudRequest<Self>;
+    fn get<A: PgAcquire>(db: A) -> CrudRequest<Self, A>;
+
+    /// Compares table in the database with entity fields and fails with
+    /// `SchemaError` listing all differences
+    async fn verify_schema(db: impl PgAcquire) -> Result<()>;
+
+    /// Entity referenced by `field` of this entity:
+    /// `wallet.belongs_to::<User>(Wallet::USER_ID, db)`
+    async fn belongs_to<P: Crud>(&self, field: Field<Self>, db: impl PgAcquire) -> Result<P>;
+
+    /// Entities referencing this entity with `field`:
+    /// `user.has_many(Wallet::USER_ID, db)`
+    async fn has_many<C: Crud>(&self, field: Field<C>, db: impl PgAcquire) -> Result<Vec<C>>;
+
+    /// Loads entities referencing any of `entities` with `field` in one query.
+    /// Result is grouped by referenced id and has an entry for each of
+    /// `entities`.
+    async fn load_related<C: Crud>(
+        entities: &[Self],
+        field: Field<C>,
+        db: impl PgAcquire,
+    ) -> Result<HashMap<EntityId, Vec<C>>>;
+
+    /// Loads entities referenced by `field` of `entities` in one query.
+    /// Result is keyed by referenced id.
+    async fn load_referenced<P: Crud>(
+        entities: &[Self],
+        field: Field<Self>,
+        db: impl PgAcquire,
+    ) -> Result<HashMap<EntityId, P>>;
 }
 
+const MAX_BIND_PARAMETERS: usize = u16::MAX as usize;
+
 impl<T: Entity> Crud for T {
-    async fn create_table(pool: &PgPool) -> Result<()> {
-        pool.execute(&*T::create_table_query()).await?;
+    async fn create_table(db: impl PgAcquire) -> Result<()> {
+        let mut conn = db.acquire().await?;
+        conn.execute(&*T::create_table_query()).await?;
         Ok(())
     }
 
-    async fn drop_table(pool: &PgPool) -> Result<()> {
-        pool.execute(&*format!("DROP TABLE IF EXISTS {};", T::table_name())).await?;
+    async fn drop_table(db: impl PgAcquire) -> Result<()> {
+        let mut conn = db.acquire().await?;
+        conn.execute(&*format!("DROP TABLE IF EXISTS {};", T::qualified_table_name()))
+            .await?;
         Ok(())
     }
 
-    async fn insert(self, pool: &PgPool) -> Result<Self> {
+    async fn insert(self, db: impl PgAcquire) -> Result<Self> {
         let query = T::insert_query();
         let query = sqlx::query_as::<Postgres, T>(&query);
         let query = self.bind_to_sqlx_query(query);
 
-        Ok(query.fetch_one(pool).await?)
+        let mut conn = db.acquire().await?;
+
+        Ok(query.fetch_one(&mut *conn).await?)
     }
 
-    async fn get_all(pool: &PgPool) -> Result<Vec<Self>> {
-        Ok(sqlx::query_as(&format!("SELECT * FROM {}", T::table_name()))
-            .fetch_all(pool)
-            .await?)
+    async fn insert_many(entities: Vec<Self>, db: impl PgAcquire) -> Result<Vec<Self>> {
+        let fields_count = T::fields().iter().filter(|f| !f.is_id()).count().max(1);
+        let chunk_size = MAX_BIND_PARAMETERS / fields_count;
+
+        let mut conn = db.acquire().await?;
+
+        let mut result = Vec::with_capacity(entities.len());
+        let mut entities = entities.into_iter().peekable();
+
+        while entities.peek().is_some() {
+            let chunk: Vec<_> = entities.by_ref().take(chunk_size).collect();
+
+            let query = T::insert_many_query(chunk.len());
+            let mut query = sqlx::query_as::<Postgres, T>(&query);
+
+            for entity in chunk {
+                query = entity.bind_to_sqlx_query(query);
+            }
+
+            result.extend(query.fetch_all(&mut *conn).await?);
+        }
+
+        Ok(result)
     }
 
-    async fn with_id(id: ID, pool: &PgPool) -> Result<Self> {
-        Ok(
-            sqlx::query_as(&format!("SELECT * FROM {} WHERE id = {id}", T::table_name()))
-                .fetch_one(pool)
-                .await?,
-        )
+    async fn upsert(self, conflict_fields: &[Field<Self>], db: impl PgAcquire) -> Result<Self> {
+        let query = T::upsert_query(conflict_fields);
+        let query = sqlx::query_as::<Postgres, T>(&query);
+        let query = self.bind_to_sqlx_query(query);
+
+        let mut conn = db.acquire().await?;
+
+        Ok(query.fetch_one(&mut *conn).await?)
     }
 
-    async fn delete(self, pool: &PgPool) -> Result<()> {
-        let id: ID = self.value_by_name("id").parse()?;
+    async fn get_all(db: impl PgAcquire) -> Result<Vec<Self>> {
+        Self::get(db).all().await
+    }
 
-        query(&format!("DELETE FROM {} WHERE id = $1", T::table_name()))
-            .bind(id)
-            .execute(pool)
-            .await?;
+    async fn with_id(id: impl Into<EntityId>, db: impl PgAcquire) -> Result<Self> {
+        Self::get(db)
+            .with(T::id_field()?, id.into())
+            .one()
+            .await?
+            .ok_or_else(|| sqlx::Error::RowNotFound.into())
+    }
+
+    async fn find(key: &[EntityId], db: impl PgAcquire) -> Result<Option<Self>> {
+        key_request(key, db)?.one().await
+    }
+
+    async fn delete_by_key(key: &[EntityId], db: impl PgAcquire) -> Result<()> {
+        key_request::<T, _>(key, db)?.delete().await?;
+        Ok(())
+    }
+
+    async fn delete(self, db: impl PgAcquire) -> Result<()> {
+        if !T::soft_delete() {
+            return self.force_delete(db).await;
+        }
+
+        let table = T::qualified_table_name();
+
+        let mut assignments = vec!["deleted_at = now()".to_string()];
+        assignments.extend(managed_updates::<T>(&table));
+
+        let sql = format!(
+            "UPDATE {table} SET {} WHERE {}",
+            assignments.join(", "),
+            T::key_condition(1)
+        );
+        let mut query = query(&sql);
+
+        for value in self.key_values()? {
+            query = query.bind(value);
+        }
+
+        let mut conn = db.acquire().await?;
+
+        query.execute(&mut *conn).await?;
+
+        Ok(())
+    }
+
+    async fn force_delete(self, db: impl PgAcquire) -> Result<()> {
+        let sql = format!(
+            "DELETE FROM {} WHERE {}",
+            T::qualified_table_name(),
+            T::key_condition(1)
+        );
+        let mut query = query(&sql);
+
+        for value in self.key_values()? {
+            query = query.bind(value);
+        }
+
+        let mut conn = db.acquire().await?;
+
+        query.execute(&mut *conn).await?;
 
         Ok(())
     }
 
-    fn get(pool: &PgPool) -> CrudRequest<Self> {
-        CrudRequest::new(pool)
+    async fn restore(self, db: impl PgAcquire) -> Result<Self> {
+        if !T::soft_delete() {
+            bail!("{} doesn't use soft delete", T::type_name());
+        }
+
+        let table = T::qualified_table_name();
+
+        let mut assignments = vec!["deleted_at = NULL".to_string()];
+        assignments.extend(managed_updates::<T>(&table));
+
+        let sql = format!(
+            "UPDATE {table} SET {} WHERE {} RETURNING *",
+            assignments.join(", "),
+            T::key_condition(1)
+        );
+        let mut query = sqlx::query_as(&sql);
+
+        for value in self.key_values()? {
+            query = query.bind(value);
+        }
+
+        let mut conn = db.acquire().await?;
+
+        Ok(query.fetch_one(&mut *conn).await?)
+    }
+
+    async fn update(&self, db: impl PgAcquire) -> Result<Self>
+    where Self: Clone {
+        update_with(self, &T::update_query()?, db).await
+    }
+
+    async fn update_fields(&self, fields: &[Field<Self>], db: impl PgAcquire) -> Result<Self>
+    where Self: Clone {
+        let key = T::key();
+
+        if fields.iter().all(|field| field.is_id() || key.contains(field)) {
+            return Self::find(&self.key_values()?, db)
+                .await?
+                .ok_or_else(|| sqlx::Error::RowNotFound.into());
+        }
+
+        update_with(self, &T::update_fields_query(fields)?, db).await
+    }
+
+    fn get<A: PgAcquire>(db: A) -> CrudRequest<Self, A> {
+        CrudRequest::new(db)
+    }
+
+    async fn verify_schema(db: impl PgAcquire) -> Result<()> {
+        verify_schemas(&[EntitySchema::of::<Self>()], db).await
+    }
+
+    async fn belongs_to<P: Crud>(&self, field: Field<Self>, db: impl PgAcquire) -> Result<P> {
+        let id = id_value(self, field)?;
+        P::with_id(id, db).await
+    }
+
+    async fn has_many<C: Crud>(&self, field: Field<C>, db: impl PgAcquire) -> Result<Vec<C>> {
+        C::get(db).with(field, self.entity_id()?).all().await
+    }
+
+    async fn load_related<C: Crud>(
+        entities: &[Self],
+        field: Field<C>,
+        db: impl PgAcquire,
+    ) -> Result<HashMap<EntityId, Vec<C>>> {
+        let ids = entities.iter().map(Entity::entity_id).collect::<Result<Vec<_>>>()?;
+
+        let mut related: HashMap<EntityId, Vec<C>> = ids.iter().map(|id| (*id, vec![])).collect();
+
+        if ids.is_empty() {
+            return Ok(related);
+        }
+
+        let request = C::key_fields()
+            .into_iter()
+            .fold(C::get(db).in_ids(field, ids), |request, key| request.order_by(key, Order::Asc));
+
+        for entity in request.all().await? {
+            let id = id_value(&entity, field)?;
+            related.entry(id).or_default().push(entity);
+        }
+
+        Ok(related)
+    }
+
+    async fn load_referenced<P: Crud>(
+        entities: &[Self],
+        field: Field<Self>,
+        db: impl PgAcquire,
+    ) -> Result<HashMap<EntityId, P>> {
+        let mut ids = entities
+            .iter()
+            .map(|entity| id_value(entity, field))
+            .collect::<Result<Vec<_>>>()?;
+        ids.sort_unstable();
+        ids.dedup();
+
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        P::get(db)
+            .in_ids(P::id_field()?, ids)
+            .all()
+            .await?
+            .into_iter()
+            .map(|entity| Ok((entity.entity_id()?, entity)))
+            .collect()
+    }
+}
+
+/// Runs query of `update_fields_query` binding all fields of `entity`
+async fn update_with<T: Entity + Clone>(entity: &T, sql: &str, db: impl PgAcquire) -> Result<T> {
+    let key = entity.key_values()?;
+
+    let mut query = entity.clone().bind_to_sqlx_query(sqlx::query_as::<Postgres, T>(sql));
+
+    // Composite key fields are bound with the rest of the fields
+    if T::key().is_empty() {
+        query = query.bind(key[0]);
+    }
+
+    let mut conn = db.acquire().await?;
+
+    updated::<T>(query.fetch_optional(&mut *conn).await?, &key)
+}
+
+/// Missing row of versioned entity means that its version has changed
+fn updated<T: Entity>(entity: Option<T>, key: &[EntityId]) -> Result<T> {
+    match entity {
+        Some(entity) => Ok(entity),
+        None if T::versioned() => Err(ConcurrencyError {
+            entity: T::type_name(),
+            id:     key.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+        }
+        .into()),
+        None => Err(sqlx::Error::RowNotFound.into()),
+    }
+}
+
+/// Request filtering by primary key: `Entity::key` fields or `id`
+fn key_request<T: Entity, A: PgAcquire>(key: &[EntityId], db: A) -> Result<CrudRequest<T, A>> {
+    let fields = T::key_fields();
+
+    if fields.len() != key.len() {
+        bail!(
+            "{} key has {} fields but {} values were given",
+            T::type_name(),
+            fields.len(),
+            key.len()
+        );
     }
+
+    Ok(fields
+        .into_iter()
+        .zip(key.iter().copied())
+        .fold(T::get(db), |request, (field, value)| request.with(field, value)))
 }
 
 #[cfg(test)]
 mod test {
+    use std::pin::pin;
+
     use anyhow::Result;
-    use reflected::{Reflected, ToReflectedVal};
-    use sqlx::FromRow;
+    use futures::TryStreamExt;
+    use reflected::{Field, Reflected, ToReflectedVal};
+    use rust_decimal::Decimal;
+    use sqlx::{Executor, FromRow};
 
-    use crate::{db::prepare_db, field_extension::FieldExtension, server::crud::Crud};
+    use crate::{
+        Column, ConcurrencyError, DateTime, Entity, EntityId, EntitySchema, PrimaryKey, SchemaError,
+        SchemaMismatch, Uuid,
+        db::prepare_db,
+        field_extension::FieldExtension,
+        server::crud::{Crud, Order},
+        verify_schemas,
+    };
 
     #[derive(
         strum::Display,
@@ -108,6 +426,8 @@ mod test {
         tp:     WalletType,
     }
 
+    impl Entity for VaccinatedDog {}
+
     #[tokio::test]
     async fn test() -> Result<()> {
         let pool = prepare_db().await?;
@@ -182,6 +502,62 @@ mod test {
         assert_eq!(VaccinatedDog::AGE.one_where(7564, &pool).await?, None);
         assert_eq!(VaccinatedDog::AGE.all_where(7564, &pool).await?, vec![]);
 
+        assert_eq!(
+            VaccinatedDog::get(&pool).gt(VaccinatedDog::AGE, 100).all().await?,
+            vec![dog.clone()]
+        );
+        assert_eq!(
+            VaccinatedDog::get(&pool).lte(VaccinatedDog::AGE, 100).all().await?,
+            vec![]
+        );
+        assert_eq!(
+            VaccinatedDog::get(&pool).ilike(VaccinatedDog::NAME, "FED%").one().await?,
+            Some(dog.clone())
+        );
+        assert_eq!(
+            VaccinatedDog::get(&pool)
+                .in_list(VaccinatedDog::NAME, ["rex".to_string(), "fedie".to_string()])
+                .all()
+                .await?,
+            vec![dog.clone()]
+        );
+        assert_eq!(
+            VaccinatedDog::get(&pool)
+                .ne(VaccinatedDog::NAME, "fedie")
+                .is_not_null(VaccinatedDog::NAME)
+                .all()
+                .await?,
+            vec![]
+        );
+        assert_eq!(
+            VaccinatedDog::get(&pool)
+                .or(|f| f.with(VaccinatedDog::NAME, "rex").with(VaccinatedDog::AGE, 4234))
+                .with(VaccinatedDog::WEIGHT, 5.125)
+                .all()
+                .await?,
+            vec![dog.clone()]
+        );
+
+        let mut dog = dog;
+
+        dog.name = "bobik".to_string();
+        dog.weight = 10.5;
+
+        assert_eq!(dog.update(&pool).await?, dog);
+        assert_eq!(VaccinatedDog::with_id(1, &pool).await?, dog);
+
+        let mut changed_dog = dog.clone();
+
+        changed_dog.age = 5;
+        changed_dog.name = "rex".to_string();
+
+        let partially_updated = changed_dog.update_fields(&[VaccinatedDog::AGE], &pool).await?;
+
+        assert_eq!(partially_updated.age, 5);
+        assert_eq!(partially_updated.name, "bobik");
+
+        dog = partially_updated;
+
         dog.delete(&pool).await?;
 
         assert_eq!(VaccinatedDog::get_all(&pool).await?, vec![]);
@@ -190,4 +566,1175 @@ mod test {
 
         Ok(())
     }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct SyncedWallet {
+        id:          i32,
+        external_id: String,
+        amount:      i32,
+    }
+
+    impl Entity for SyncedWallet {}
+
+    #[tokio::test]
+    async fn upsert() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        SyncedWallet::drop_table(&pool).await?;
+        SyncedWallet::create_table(&pool).await?;
+
+        pool.execute("CREATE UNIQUE INDEX synced_wallets_external_id_key ON synced_wallets (external_id);")
+            .await?;
+
+        let wallet = SyncedWallet {
+            id:          0,
+            external_id: "ext_1".to_string(),
+            amount:      100,
+        };
+
+        let inserted = wallet.clone().upsert(&[SyncedWallet::EXTERNAL_ID], &pool).await?;
+
+        assert_eq!(inserted.amount, 100);
+
+        let updated = SyncedWallet {
+            amount: 200,
+            ..wallet
+        }
+        .upsert(&[SyncedWallet::EXTERNAL_ID], &pool)
+        .await?;
+
+        assert_eq!(updated.id, inserted.id);
+        assert_eq!(updated.amount, 200);
+
+        assert_eq!(SyncedWallet::get_all(&pool).await?, vec![updated]);
+
+        SyncedWallet::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Parrot {
+        id:  i32,
+        age: i32,
+    }
+
+    impl Entity for Parrot {}
+
+    #[tokio::test]
+    async fn pagination() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Parrot::drop_table(&pool).await?;
+        Parrot::create_table(&pool).await?;
+
+        for age in [3, 1, 5, 2, 4] {
+            Parrot { id: 0, age }.insert(&pool).await?;
+        }
+
+        let ages = |parrots: Vec<Parrot>| parrots.into_iter().map(|p| p.age).collect::<Vec<_>>();
+
+        assert_eq!(
+            ages(Parrot::get(&pool).order_by(Parrot::AGE, Order::Asc).all().await?),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        assert_eq!(
+            ages(
+                Parrot::get(&pool)
+                    .order_by(Parrot::AGE, Order::Desc)
+                    .limit(2)
+                    .offset(1)
+                    .all()
+                    .await?
+            ),
+            vec![4, 3]
+        );
+
+        assert_eq!(
+            ages(Parrot::get(&pool).after(Parrot::AGE, 2).limit(2).all().await?),
+            vec![3, 4]
+        );
+
+        let page = Parrot::get(&pool)
+            .gt(Parrot::AGE, 1)
+            .order_by(Parrot::AGE, Order::Asc)
+            .page(1, 3)
+            .await?;
+
+        assert_eq!(page.total, 4);
+        assert_eq!(ages(page.items), vec![5]);
+
+        Parrot::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Hamster {
+        id:     i32,
+        weight: i32,
+    }
+
+    impl Entity for Hamster {}
+
+    #[tokio::test]
+    async fn aggregates() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Hamster::drop_table(&pool).await?;
+        Hamster::create_table(&pool).await?;
+
+        assert_eq!(Hamster::get(&pool).count().await?, 0);
+        assert!(!Hamster::get(&pool).exists().await?);
+        assert_eq!(Hamster::get(&pool).sum::<i64>(Hamster::WEIGHT).await?, None);
+
+        for weight in [10, 20, 30, 45] {
+            Hamster { id: 0, weight }.insert(&pool).await?;
+        }
+
+        assert_eq!(Hamster::get(&pool).count().await?, 4);
+        assert_eq!(Hamster::get(&pool).gt(Hamster::WEIGHT, 15).count().await?, 3);
+        assert!(Hamster::get(&pool).with(Hamster::WEIGHT, 45).exists().await?);
+        assert!(!Hamster::get(&pool).with(Hamster::WEIGHT, 46).exists().await?);
+
+        assert_eq!(Hamster::get(&pool).sum::<i64>(Hamster::WEIGHT).await?, Some(105));
+        assert_eq!(
+            Hamster::get(&pool).lt(Hamster::WEIGHT, 40).sum::<i64>(Hamster::WEIGHT).await?,
+            Some(60)
+        );
+        assert_eq!(Hamster::get(&pool).min::<i32>(Hamster::WEIGHT).await?, Some(10));
+        assert_eq!(Hamster::get(&pool).max::<i32>(Hamster::WEIGHT).await?, Some(45));
+        assert_eq!(
+            Hamster::get(&pool).avg::<Decimal>(Hamster::WEIGHT).await?,
+            Some(Decimal::new(2625, 2))
+        );
+
+        Hamster::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Turtle {
+        id:      i32,
+        owner:   i32,
+        speed:   i32,
+        shelled: i32,
+    }
+
+    impl Entity for Turtle {}
+
+    #[tokio::test]
+    async fn bulk_update_and_delete() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Turtle::drop_table(&pool).await?;
+        Turtle::create_table(&pool).await?;
+
+        for (owner, speed) in [(1, 1), (1, 2), (1, 3), (2, 4), (2, 5)] {
+            Turtle {
+                id: 0,
+                owner,
+                speed,
+                shelled: 0,
+            }
+            .insert(&pool)
+            .await?;
+        }
+
+        assert_eq!(
+            Turtle::get(&pool)
+                .with(Turtle::OWNER, 1)
+                .gt(Turtle::SPEED, 1)
+                .update_set(Turtle::SHELLED, 1)
+                .await?,
+            2
+        );
+
+        assert_eq!(Turtle::get(&pool).with(Turtle::SHELLED, 1).count().await?, 2);
+        assert_eq!(Turtle::get(&pool).with(Turtle::OWNER, 2).delete().await?, 2);
+        assert_eq!(Turtle::get(&pool).with(Turtle::OWNER, 2).delete().await?, 0);
+        assert_eq!(Turtle::get(&pool).count().await?, 3);
+        assert_eq!(Turtle::get(&pool).delete().await?, 3);
+
+        Turtle::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Sparrow {
+        id:     i32,
+        name:   String,
+        age:    i32,
+        weight: f32,
+    }
+
+    impl Entity for Sparrow {}
+
+    #[tokio::test]
+    async fn insert_many() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Sparrow::drop_table(&pool).await?;
+        Sparrow::create_table(&pool).await?;
+
+        assert_eq!(Sparrow::insert_many(vec![], &pool).await?, vec![]);
+
+        let sparrows: Vec<_> = (0..30_000)
+            .map(|i| Sparrow {
+                id:     0,
+                name:   format!("sparrow_{i}"),
+                age:    i,
+                weight: 0.5,
+            })
+            .collect();
+
+        let inserted = Sparrow::insert_many(sparrows.clone(), &pool).await?;
+
+        assert_eq!(inserted.len(), sparrows.len());
+
+        for (inserted, sparrow) in inserted.iter().zip(&sparrows) {
+            assert_eq!(inserted.name, sparrow.name);
+            assert_eq!(inserted.age, sparrow.age);
+            assert_ne!(inserted.id, 0);
+        }
+
+        assert_eq!(Sparrow::get(&pool).count().await?, 30_000);
+
+        Sparrow::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Swallow {
+        id:   i32,
+        name: String,
+        age:  i32,
+    }
+
+    impl Entity for Swallow {}
+
+    #[tokio::test]
+    async fn stream() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Swallow::drop_table(&pool).await?;
+        Swallow::create_table(&pool).await?;
+
+        let swallows: Vec<_> = (0..100)
+            .map(|i| Swallow {
+                id:   0,
+                name: format!("swallow_{i}"),
+                age:  i,
+            })
+            .collect();
+
+        let inserted = Swallow::insert_many(swallows, &pool).await?;
+
+        let stream = Swallow::get(&pool)
+            .gte(Swallow::AGE, 50)
+            .order_by(Swallow::ID, Order::Asc)
+            .stream();
+        let streamed: Vec<Swallow> = stream.try_collect().await?;
+
+        assert_eq!(streamed, inserted[50..]);
+
+        let mut stream = pin!(Swallow::get(&pool).stream());
+        let mut count = 0;
+
+        while let Some(swallow) = stream.try_next().await? {
+            assert!(swallow.name.starts_with("swallow_"));
+            count += 1;
+        }
+
+        assert_eq!(count, 100);
+
+        Swallow::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Owl {
+        id:        i32,
+        name:      String,
+        nickname:  Option<String>,
+        wingspan:  f64,
+        feathers:  i64,
+        nocturnal: bool,
+        price:     Decimal,
+        born:      DateTime,
+        ringed:    Option<DateTime>,
+    }
+
+    impl Entity for Owl {}
+
+    #[tokio::test]
+    async fn column_types() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Owl::drop_table(&pool).await?;
+        Owl::create_table(&pool).await?;
+
+        let born = DateTime::parse_from_str("2024-05-01 12:30:00", "%Y-%m-%d %H:%M:%S")?;
+
+        let wild = Owl {
+            id: 0,
+            name: "wild".into(),
+            nickname: None,
+            wingspan: 1.25,
+            feathers: 10_000_000_000,
+            nocturnal: true,
+            price: Decimal::new(1999, 2),
+            born,
+            ringed: None,
+        }
+        .insert(&pool)
+        .await?;
+
+        let tame = Owl {
+            id: 0,
+            name: "tame".into(),
+            nickname: Some("Hedwig".into()),
+            wingspan: 0.9,
+            feathers: 7_000,
+            nocturnal: false,
+            price: Decimal::new(5, 0),
+            born,
+            ringed: Some(born),
+        }
+        .insert(&pool)
+        .await?;
+
+        assert_eq!(Owl::with_id(wild.id, &pool).await?, wild);
+        assert_eq!(Owl::with_id(tame.id, &pool).await?, tame);
+        assert_eq!(wild.feathers, 10_000_000_000);
+        assert_eq!(tame.ringed, Some(born));
+
+        assert_eq!(Owl::get(&pool).is_null(Owl::RINGED).all().await?, vec![wild]);
+
+        Owl::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Duck {
+        id:   i32,
+        name: String,
+        age:  i32,
+    }
+
+    impl Entity for Duck {
+        fn columns() -> Vec<Column<Self>> {
+            vec![
+                Column::new(Duck::NAME).unique().varchar(16).index(),
+                Column::new(Duck::AGE).check("age >= 0"),
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn constraints() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Duck::drop_table(&pool).await?;
+        Duck::create_table(&pool).await?;
+
+        let duck = |name: &str, age| Duck {
+            id: 0,
+            name: name.into(),
+            age,
+        };
+
+        duck("Donald", 3).insert(&pool).await?;
+
+        let duplicate = duck("Donald", 4).insert(&pool).await.unwrap_err().to_string();
+        assert!(duplicate.contains("duplicate key value violates unique constraint \"ducks_name_key\""));
+
+        let negative = duck("Daisy", -1).insert(&pool).await.unwrap_err().to_string();
+        assert!(negative.contains("violates check constraint"));
+
+        let too_long = duck("Donald the Magnificent", 1).insert(&pool).await;
+        assert!(too_long.is_err());
+
+        let indexes: Vec<String> =
+            sqlx::query_scalar("SELECT indexname FROM pg_indexes WHERE tablename = 'ducks'")
+                .fetch_all(&pool)
+                .await?;
+        assert!(indexes.contains(&"ducks_name_idx".to_string()));
+
+        Duck::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Farmer {
+        id:   i32,
+        name: String,
+    }
+
+    impl Entity for Farmer {}
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Cow {
+        id:        i32,
+        farmer_id: i32,
+        name:      String,
+    }
+
+    impl Entity for Cow {
+        fn columns() -> Vec<Column<Self>> {
+            vec![Column::new(Cow::FARMER_ID).references::<Farmer>().index()]
+        }
+    }
+
+    #[tokio::test]
+    async fn relations() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Cow::drop_table(&pool).await?;
+        Farmer::drop_table(&pool).await?;
+        Farmer::create_table(&pool).await?;
+        Cow::create_table(&pool).await?;
+
+        let farmer = |name: &str| Farmer {
+            id:   0,
+            name: name.into(),
+        };
+
+        let cow = |farmer: &Farmer, name: &str| Cow {
+            id:        0,
+            farmer_id: farmer.id,
+            name:      name.into(),
+        };
+
+        let john = farmer("John").insert(&pool).await?;
+        let mary = farmer("Mary").insert(&pool).await?;
+        let lazy = farmer("Lazy").insert(&pool).await?;
+
+        let cows = Cow::insert_many(
+            vec![cow(&john, "Bella"), cow(&mary, "Daisy"), cow(&john, "Molly")],
+            &pool,
+        )
+        .await?;
+
+        assert!(
+            Cow {
+                id:        0,
+                farmer_id: 100_500,
+                name:      "Ghost".into(),
+            }
+            .insert(&pool)
+            .await
+            .is_err()
+        );
+
+        assert_eq!(cows[0].belongs_to::<Farmer>(Cow::FARMER_ID, &pool).await?, john);
+        assert_eq!(cows[1].belongs_to::<Farmer>(Cow::FARMER_ID, &pool).await?, mary);
+
+        assert_eq!(
+            john.has_many(Cow::FARMER_ID, &pool).await?,
+            vec![cows[0].clone(), cows[2].clone()]
+        );
+        assert_eq!(lazy.has_many(Cow::FARMER_ID, &pool).await?, vec![]);
+
+        let farmers = vec![john.clone(), mary.clone(), lazy.clone()];
+
+        let related = Farmer::load_related(&farmers, Cow::FARMER_ID, &pool).await?;
+
+        assert_eq!(related.len(), 3);
+        assert_eq!(
+            related[&EntityId::Int(john.id)],
+            vec![cows[0].clone(), cows[2].clone()]
+        );
+        assert_eq!(related[&EntityId::Int(mary.id)], vec![cows[1].clone()]);
+        assert_eq!(related[&EntityId::Int(lazy.id)], vec![]);
+
+        let referenced = Cow::load_referenced::<Farmer>(&cows, Cow::FARMER_ID, &pool).await?;
+
+        assert_eq!(referenced.len(), 2);
+        assert_eq!(referenced[&EntityId::Int(john.id)], john);
+        assert_eq!(referenced[&EntityId::Int(mary.id)], mary);
+
+        assert!(Farmer::load_related::<Cow>(&[], Cow::FARMER_ID, &pool).await?.is_empty());
+
+        Cow::drop_table(&pool).await?;
+        Farmer::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Comet {
+        id:   i64,
+        name: String,
+    }
+
+    impl Entity for Comet {
+        fn primary_key() -> PrimaryKey {
+            PrimaryKey::BigSerial
+        }
+    }
+
+    #[tokio::test]
+    async fn bigserial_id() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Comet::drop_table(&pool).await?;
+        Comet::create_table(&pool).await?;
+
+        pool.execute("SELECT setval(pg_get_serial_sequence('comets', 'id'), 10000000000);")
+            .await?;
+
+        let mut halley = Comet {
+            id:   0,
+            name: "Halley".into(),
+        }
+        .insert(&pool)
+        .await?;
+
+        assert_eq!(halley.id, 10_000_000_001);
+        assert_eq!(Comet::with_id(halley.id, &pool).await?, halley);
+
+        halley.name = "1P/Halley".into();
+        assert_eq!(halley.update(&pool).await?, halley);
+        assert_eq!(
+            Comet::get(&pool).with(Comet::ID, halley.id).one().await?,
+            Some(halley.clone())
+        );
+
+        halley.delete(&pool).await?;
+        assert!(Comet::get_all(&pool).await?.is_empty());
+
+        Comet::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Planet {
+        id:   Uuid,
+        name: String,
+    }
+
+    impl Entity for Planet {
+        fn table_name() -> String {
+            "legacy_planets".into()
+        }
+
+        fn schema() -> Option<&'static str> {
+            Some("space")
+        }
+
+        fn primary_key() -> PrimaryKey {
+            PrimaryKey::Uuid
+        }
+
+        fn uuid_value(&self, field: Field<Self>) -> Option<Uuid> {
+            (field == Self::ID).then_some(self.id)
+        }
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Moon {
+        #[sqlx(rename = "moon_id")]
+        id:        i32,
+        planet_id: Uuid,
+        name:      String,
+    }
+
+    impl Entity for Moon {
+        fn schema() -> Option<&'static str> {
+            Some("space")
+        }
+
+        fn id_column() -> &'static str {
+            "moon_id"
+        }
+
+        fn columns() -> Vec<Column<Self>> {
+            vec![Column::new(Moon::PLANET_ID).references::<Planet>()]
+        }
+
+        fn uuid_value(&self, field: Field<Self>) -> Option<Uuid> {
+            (field == Self::PLANET_ID).then_some(self.planet_id)
+        }
+    }
+
+    #[tokio::test]
+    async fn uuid_id_and_schema() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Moon::drop_table(&pool).await?;
+        Planet::drop_table(&pool).await?;
+        Planet::create_table(&pool).await?;
+        Moon::create_table(&pool).await?;
+
+        let earth = Planet {
+            id:   Uuid::default(),
+            name: "Earth".into(),
+        }
+        .insert(&pool)
+        .await?;
+
+        let mars = Planet {
+            id:   Uuid::default(),
+            name: "Mars".into(),
+        }
+        .insert(&pool)
+        .await?;
+
+        assert_ne!(earth.id, Uuid::default());
+        assert_eq!(Planet::with_id(earth.id, &pool).await?, earth);
+
+        let moon = |planet: &Planet, name: &str| Moon {
+            id:        0,
+            planet_id: planet.id,
+            name:      name.into(),
+        };
+
+        let moons = Moon::insert_many(
+            vec![moon(&earth, "Moon"), moon(&mars, "Phobos"), moon(&mars, "Deimos")],
+            &pool,
+        )
+        .await?;
+
+        assert_ne!(moons[0].id, 0);
+        assert_eq!(Moon::with_id(moons[1].id, &pool).await?, moons[1]);
+        assert_eq!(
+            Moon::get(&pool).with(Moon::ID, moons[2].id).one().await?,
+            Some(moons[2].clone())
+        );
+
+        assert_eq!(
+            mars.has_many(Moon::PLANET_ID, &pool).await?,
+            vec![moons[1].clone(), moons[2].clone()]
+        );
+
+        assert_eq!(moons[1].belongs_to::<Planet>(Moon::PLANET_ID, &pool).await?, mars);
+
+        let planets = Planet::load_related(&[earth.clone(), mars.clone()], Moon::PLANET_ID, &pool).await?;
+        assert_eq!(planets[&EntityId::Uuid(mars.id)], vec![moons[1].clone(), moons[2].clone()]);
+
+        let referenced = Moon::load_referenced::<Planet>(&moons, Moon::PLANET_ID, &pool).await?;
+        assert_eq!(referenced[&EntityId::Uuid(earth.id)], earth);
+
+        moons[0].clone().delete(&pool).await?;
+        assert_eq!(Moon::get(&pool).count().await?, 2);
+
+        Moon::drop_table(&pool).await?;
+        Planet::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Ledger {
+        id:         i32,
+        balance:    i32,
+        created_at: DateTime,
+        updated_at: DateTime,
+        version:    i32,
+    }
+
+    impl Entity for Ledger {
+        fn timestamps() -> bool {
+            true
+        }
+
+        fn versioned() -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn timestamps_and_versions() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Ledger::drop_table(&pool).await?;
+        Ledger::create_table(&pool).await?;
+
+        let ledger = Ledger {
+            balance: 100,
+            ..Default::default()
+        }
+        .insert(&pool)
+        .await?;
+
+        assert_eq!(ledger.version, 1);
+        assert_ne!(ledger.created_at, DateTime::default());
+        assert_eq!(ledger.created_at, ledger.updated_at);
+
+        let mut first = ledger.clone();
+        let mut second = ledger.clone();
+
+        first.balance = 50;
+        let first = first.update(&pool).await?;
+
+        assert_eq!(first.version, 2);
+        assert_eq!(first.balance, 50);
+        assert_eq!(first.created_at, ledger.created_at);
+        assert!(first.updated_at > ledger.updated_at);
+
+        second.balance = 200;
+        let error = second.update(&pool).await.unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<ConcurrencyError>(),
+            Some(&ConcurrencyError {
+                entity: "Ledger",
+                id:     ledger.id.to_string(),
+            })
+        );
+
+        assert!(second.update_fields(&[Ledger::BALANCE], &pool).await.is_err());
+        assert_eq!(Ledger::with_id(ledger.id, &pool).await?.balance, 50);
+
+        let updated = Ledger::get(&pool).update_set(Ledger::BALANCE, 70).await?;
+        assert_eq!(updated, 1);
+
+        let reloaded = Ledger::with_id(ledger.id, &pool).await?;
+        assert_eq!(reloaded.version, 3);
+        assert!(reloaded.updated_at > first.updated_at);
+
+        Ledger::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Postcard {
+        id:         i32,
+        text:       String,
+        deleted_at: Option<DateTime>,
+    }
+
+    impl Entity for Postcard {
+        fn soft_delete() -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn soft_delete() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Postcard::drop_table(&pool).await?;
+        Postcard::create_table(&pool).await?;
+
+        let cards = Postcard::insert_many(
+            ["Paris", "Rome", "Oslo"]
+                .into_iter()
+                .map(|text| Postcard {
+                    text: text.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            &pool,
+        )
+        .await?;
+
+        let paris = cards[0].clone();
+        assert_eq!(paris.deleted_at, None);
+
+        paris.clone().delete(&pool).await?;
+
+        assert!(Postcard::with_id(paris.id, &pool).await.is_err());
+        assert_eq!(Postcard::get_all(&pool).await?.len(), 2);
+        assert_eq!(Postcard::TEXT.one_where("Paris", &pool).await?, None);
+        assert_eq!(Postcard::get(&pool).with_deleted().count().await?, 3);
+
+        let deleted = Postcard::get(&pool).only_deleted().all().await?;
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].text, "Paris");
+        assert!(deleted[0].deleted_at.is_some());
+
+        let restored = deleted[0].clone().restore(&pool).await?;
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(Postcard::with_id(paris.id, &pool).await?, restored);
+
+        assert_eq!(
+            Postcard::get(&pool).with(Postcard::TEXT, "Rome").delete().await?,
+            1
+        );
+        assert_eq!(Postcard::get(&pool).count().await?, 2);
+
+        cards[2].clone().force_delete(&pool).await?;
+        assert_eq!(Postcard::get(&pool).with_deleted().count().await?, 2);
+
+        assert_eq!(Postcard::get(&pool).only_deleted().force_delete().await?, 1);
+        assert_eq!(Postcard::get(&pool).with_deleted().all().await?, vec![restored]);
+
+        Postcard::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(
+        strum::Display,
+        strum::EnumString,
+        serde::Serialize,
+        serde::Deserialize,
+        sqlx::Type,
+        Copy,
+        Clone,
+        Default,
+        PartialEq,
+        Debug,
+    )]
+    #[sqlx(type_name = "lamp_kind", rename_all = "lowercase")]
+    pub enum LampKind {
+        #[default]
+        Oil,
+        Electric,
+    }
+
+    impl ToReflectedVal<LampKind> for &str {
+        fn to_reflected_val(&self) -> std::result::Result<LampKind, String> {
+            use std::str::FromStr;
+            Ok(LampKind::from_str(self).unwrap())
+        }
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Lighthouse {
+        id:     i32,
+        name:   String,
+        height: i32,
+        keeper: Option<String>,
+        built:  DateTime,
+        lamp:   LampKind,
+    }
+
+    impl Entity for Lighthouse {}
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Buoy {
+        id:    i32,
+        depth: f64,
+        label: Option<String>,
+        tp:    WalletType,
+    }
+
+    impl Entity for Buoy {
+        fn columns() -> Vec<Column<Self>> {
+            vec![Column::new(Buoy::LABEL).varchar(20)]
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_schema() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Buoy::drop_table(&pool).await?;
+        Buoy::create_table(&pool).await?;
+
+        Buoy::verify_schema(&pool).await?;
+
+        Lighthouse::drop_table(&pool).await?;
+
+        let lamp_missing = SchemaMismatch::MissingEnumType {
+            table:     "lighthouses".to_string(),
+            column:    "lamp".to_string(),
+            type_name: "lamp_kind".to_string(),
+        };
+
+        let error = Lighthouse::verify_schema(&pool).await.unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<SchemaError>().unwrap().mismatches,
+            vec![
+                lamp_missing.clone(),
+                SchemaMismatch::MissingTable {
+                    table: "lighthouses".to_string(),
+                },
+            ]
+        );
+
+        pool.execute(
+            r"CREATE TABLE lighthouses
+(
+   id SERIAL PRIMARY KEY,
+   name VARCHAR(50) NOT NULL,
+   height BIGINT NOT NULL,
+   keeper TEXT NOT NULL,
+   lamp TEXT NOT NULL,
+   color TEXT
+);",
+        )
+        .await?;
+
+        let error = verify_schemas(
+            &[EntitySchema::of::<Buoy>(), EntitySchema::of::<Lighthouse>()],
+            &pool,
+        )
+        .await
+        .unwrap_err();
+
+        let error = error.downcast_ref::<SchemaError>().unwrap();
+
+        assert_eq!(
+            error.mismatches,
+            vec![
+                lamp_missing,
+                SchemaMismatch::TypeMismatch {
+                    table:    "lighthouses".to_string(),
+                    column:   "height".to_string(),
+                    expected: "INTEGER".to_string(),
+                    found:    "int8".to_string(),
+                },
+                SchemaMismatch::Nullability {
+                    table:    "lighthouses".to_string(),
+                    column:   "keeper".to_string(),
+                    nullable: true,
+                },
+                SchemaMismatch::MissingColumn {
+                    table:  "lighthouses".to_string(),
+                    column: "built".to_string(),
+                },
+                SchemaMismatch::ExtraColumn {
+                    table:  "lighthouses".to_string(),
+                    column: "color".to_string(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            error.to_string(),
+            "Database schema doesn't match entities:
+  enum type lamp_kind of lighthouses.lamp doesn't exist
+  lighthouses.height has type int8 instead of INTEGER
+  lighthouses.keeper is NOT NULL but entity field is optional
+  lighthouses.built doesn't exist
+  lighthouses.color has no entity field"
+        );
+
+        Lighthouse::drop_table(&pool).await?;
+        Buoy::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Membership {
+        club_id:   i32,
+        person_id: i32,
+        role:      String,
+    }
+
+    impl Entity for Membership {
+        fn key() -> Vec<Field<Self>> {
+            vec![Membership::CLUB_ID, Membership::PERSON_ID]
+        }
+    }
+
+    #[tokio::test]
+    async fn composite_key() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Membership::drop_table(&pool).await?;
+        Membership::create_table(&pool).await?;
+
+        let membership = |club_id, person_id, role: &str| Membership {
+            club_id,
+            person_id,
+            role: role.to_string(),
+        };
+
+        Membership::insert_many(
+            vec![
+                membership(1, 1, "owner"),
+                membership(1, 2, "member"),
+                membership(2, 1, "member"),
+            ],
+            &pool,
+        )
+        .await?;
+
+        assert!(membership(1, 1, "member").insert(&pool).await.is_err());
+
+        assert_eq!(
+            Membership::find(&[1.into(), 2.into()], &pool).await?,
+            Some(membership(1, 2, "member"))
+        );
+        assert_eq!(Membership::find(&[2.into(), 2.into()], &pool).await?, None);
+        assert!(Membership::find(&[1.into()], &pool).await.is_err());
+
+        assert!(Membership::with_id(1, &pool).await.is_err());
+        assert!(membership(1, 2, "member").entity_id().is_err());
+
+        let promoted = membership(1, 2, "admin").update(&pool).await?;
+        assert_eq!(promoted, membership(1, 2, "admin"));
+
+        assert!(membership(3, 3, "admin").update(&pool).await.is_err());
+
+        promoted.delete(&pool).await?;
+        Membership::delete_by_key(&[2.into(), 1.into()], &pool).await?;
+
+        assert_eq!(Membership::get_all(&pool).await?, vec![membership(1, 1, "owner")]);
+
+        Membership::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Booking {
+        room_id: Uuid,
+        night:   i32,
+        guest:   String,
+    }
+
+    impl Entity for Booking {
+        fn key() -> Vec<Field<Self>> {
+            vec![Booking::ROOM_ID, Booking::NIGHT]
+        }
+
+        fn uuid_value(&self, field: Field<Self>) -> Option<Uuid> {
+            (field == Self::ROOM_ID).then_some(self.room_id)
+        }
+    }
+
+    #[tokio::test]
+    async fn mixed_composite_key() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Booking::drop_table(&pool).await?;
+        Booking::create_table(&pool).await?;
+
+        let room = Uuid::new_v4();
+
+        let booking = |night, guest: &str| Booking {
+            room_id: room,
+            night,
+            guest: guest.to_string(),
+        };
+
+        Booking::insert_many(vec![booking(1, "alice"), booking(2, "bob")], &pool).await?;
+
+        assert_eq!(
+            booking(2, "bob").key_values()?,
+            [EntityId::Uuid(room), EntityId::Int(2)]
+        );
+
+        assert_eq!(
+            Booking::find(&booking(2, "bob").key_values()?, &pool).await?,
+            Some(booking(2, "bob"))
+        );
+
+        let moved = booking(2, "carol").update(&pool).await?;
+        assert_eq!(moved, booking(2, "carol"));
+
+        assert_eq!(
+            moved.update_fields(&[Booking::NIGHT], &pool).await?,
+            booking(2, "carol")
+        );
+
+        moved.delete(&pool).await?;
+
+        assert_eq!(Booking::get_all(&pool).await?, vec![booking(1, "alice")]);
+
+        Booking::drop_table(&pool).await?;
+
+        Ok(())
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Recipe {
+        id:    i32,
+        title: String,
+        tags:  Option<String>,
+    }
+
+    impl Entity for Recipe {
+        fn search_fields() -> Vec<Field<Self>> {
+            vec![Recipe::TITLE, Recipe::TAGS]
+        }
+    }
+
+    #[tokio::test]
+    async fn search() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Recipe::drop_table(&pool).await?;
+        Recipe::create_table(&pool).await?;
+
+        Recipe::verify_schema(&pool).await?;
+
+        let recipe = |title: &str, tags: Option<&str>| Recipe {
+            title: title.to_string(),
+            tags: tags.map(ToString::to_string),
+            ..Default::default()
+        };
+
+        let recipes = Recipe::insert_many(
+            vec![
+                recipe("Chicken soup", None),
+                recipe("Tomato salad", Some("vegetarian")),
+                recipe("Tomato soup", Some("soup vegetarian")),
+            ],
+            &pool,
+        )
+        .await?;
+
+        let titles = |recipes: Vec<Recipe>| recipes.into_iter().map(|r| r.title).collect::<Vec<_>>();
+
+        assert_eq!(
+            titles(Recipe::get(&pool).search("soup").all().await?),
+            ["Tomato soup", "Chicken soup"]
+        );
+
+        assert_eq!(
+            titles(
+                Recipe::get(&pool)
+                    .search_query("tomato & veg:*")
+                    .order_by(Recipe::ID, Order::Asc)
+                    .all()
+                    .await?
+            ),
+            ["Tomato salad", "Tomato soup"]
+        );
+
+        assert_eq!(
+            Recipe::get(&pool).search("soup").ne(Recipe::ID, recipes[2].id).all().await?,
+            vec![recipes[0].clone()]
+        );
+
+        assert_eq!(Recipe::get(&pool).search("tomato").count().await?, 2);
+        assert_eq!(
+            titles(Recipe::get(&pool).search("tomato soup").all().await?),
+            ["Tomato soup"]
+        );
+        assert!(Recipe::get(&pool).search_query("tomato soup").all().await.is_err());
+
+        let page = Recipe::get(&pool).search("soup").page(0, 1).await?;
+        assert_eq!(page.total, 2);
+        assert_eq!(titles(page.items), ["Tomato soup"]);
+
+        assert_eq!(
+            titles(Recipe::get(&pool).search_in(&[Recipe::TITLE], "vegetarian").all().await?),
+            Vec::<String>::new()
+        );
+
+        assert_eq!(
+            Recipe::get(&pool)
+                .search("salad")
+                .update_set(Recipe::TAGS, Some("cold"))
+                .await?,
+            1
+        );
+        assert_eq!(
+            titles(Recipe::get(&pool).search("cold").all().await?),
+            ["Tomato salad"]
+        );
+
+        Recipe::drop_table(&pool).await?;
+
+        Ok(())
+    }
 }
@@ -1,81 +1,745 @@
-use std::{fmt::Write, mem::transmute};
+use std::{fmt::Write, mem::replace};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
+use async_stream::try_stream;
+use futures::{Stream, TryStreamExt};
 use reflected::Field;
-use sqlx::{PgPool, Postgres, postgres::PgArguments, query::QueryAs, query_as};
+use sqlx::{Arguments, Postgres, postgres::PgArguments, query_as_with, query_scalar_with, query_with};
 
-use crate::Entity;
+use crate::{
+    Entity,
+    server::{
+        PgAcquire,
+        crud::{EntityId, Filter, Page, managed_updates, search_vector},
+    },
+};
 
-type ArgBind<T> = Box<
-    dyn FnOnce(QueryAs<'static, Postgres, T, PgArguments>) -> QueryAs<'static, Postgres, T, PgArguments>
-        + Send,
->;
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+impl Order {
+    fn to_sql(self) -> &'static str {
+        match self {
+            Self::Asc => "ASC",
+            Self::Desc => "DESC",
+        }
+    }
+}
+
+/// Soft deleted rows returned by request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Deleted {
+    Exclude,
+    Include,
+    Only,
+}
+
+impl Deleted {
+    fn to_sql<T: Entity>(self) -> Option<&'static str> {
+        if !T::soft_delete() {
+            return None;
+        }
+
+        match self {
+            Self::Exclude => Some("deleted_at IS NULL"),
+            Self::Include => None,
+            Self::Only => Some("deleted_at IS NOT NULL"),
+        }
+    }
+}
+
+/// Full text search condition
+struct Search {
+    /// SQL expression of searched `tsvector`. Generated `search` column of
+    /// `Entity::search_fields` if `None`.
+    vector:   Option<String>,
+    /// `websearch_to_tsquery` for text entered by users or `to_tsquery` for
+    /// queries in its syntax
+    function: &'static str,
+    query:    String,
+}
+
+impl Search {
+    fn vector(&self) -> &str {
+        self.vector.as_deref().unwrap_or("search")
+    }
+}
 
-type Bind<T> = (Field<T>, ArgBind<T>);
+type Cursor<T> = (Field<T>, Box<dyn FnOnce(Filter<T>, Order) -> Filter<T> + Send>);
 
-pub struct CrudRequest<'pool, T: Entity> {
-    pool:  &'pool PgPool,
-    binds: Vec<Bind<T>>,
-    q_str: String,
+pub struct CrudRequest<T: Entity, A> {
+    db:      A,
+    filter:  Filter<T>,
+    cursor:  Option<Cursor<T>>,
+    order:   Vec<(Field<T>, Order)>,
+    limit:   Option<u64>,
+    offset:  Option<u64>,
+    deleted: Deleted,
+    search:  Option<Search>,
 }
 
-impl<'pool, T: Entity> CrudRequest<'pool, T> {
-    pub(crate) fn new(pool: &'pool PgPool) -> Self {
+impl<T: Entity, A: PgAcquire> CrudRequest<T, A> {
+    pub(crate) fn new(db: A) -> Self {
         Self {
-            pool,
-            binds: vec![],
-            q_str: String::new(),
+            db,
+            filter: Filter::all(),
+            cursor: None,
+            order: vec![],
+            limit: None,
+            offset: None,
+            deleted: Deleted::Exclude,
+            search: None,
         }
     }
 
-    pub fn with<V>(mut self, field: Field<T>, value: V) -> Self
-    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
-        self.binds.push((field, Box::new(move |q| q.bind(value))));
+    fn edit_filter(mut self, edit: impl FnOnce(Filter<T>) -> Filter<T>) -> Self {
+        self.filter = edit(self.filter);
         self
     }
 
+    pub fn with<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.with(field, value))
+    }
+
     pub fn and<V>(self, field: Field<T>, value: V) -> Self
     where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
         self.with(field, value)
     }
 
-    fn prepare_query(&mut self) -> Result<QueryAs<'static, Postgres, T, PgArguments>> {
-        self.q_str = prepare_string_query(&self.binds)?;
+    pub fn ne<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.ne(field, value))
+    }
+
+    pub fn gt<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.gt(field, value))
+    }
+
+    pub fn lt<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.lt(field, value))
+    }
+
+    pub fn gte<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.gte(field, value))
+    }
+
+    pub fn lte<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.lte(field, value))
+    }
+
+    pub fn like(self, field: Field<T>, pattern: impl ToString) -> Self {
+        self.edit_filter(|f| f.like(field, pattern))
+    }
+
+    pub fn ilike(self, field: Field<T>, pattern: impl ToString) -> Self {
+        self.edit_filter(|f| f.ilike(field, pattern))
+    }
+
+    pub fn in_list<V>(self, field: Field<T>, values: impl IntoIterator<Item = V>) -> Self
+    where Vec<V>: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.edit_filter(|f| f.in_list(field, values))
+    }
 
-        // TODO:
-        // I'm too lazy and stupid to figure out these lifetimes now
-        // Query never leaves the request and this string should live inside
-        // `CrudRequest` until execution is done
-        let query_str: &'static str = unsafe { transmute(self.q_str.as_str()) };
+    pub fn in_ids(self, field: Field<T>, ids: impl IntoIterator<Item = impl Into<EntityId>>) -> Self {
+        self.edit_filter(|f| f.in_ids(field, ids))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn is_null(self, field: Field<T>) -> Self {
+        self.edit_filter(|f| f.is_null(field))
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn is_not_null(self, field: Field<T>) -> Self {
+        self.edit_filter(|f| f.is_not_null(field))
+    }
+
+    /// `Wallet::get(&db).or(|f| f.with(Wallet::NAME, "a").with(Wallet::NAME,
+    /// "b"))`
+    pub fn or(self, group: impl FnOnce(Filter<T>) -> Filter<T>) -> Self {
+        self.edit_filter(|f| f.or(group))
+    }
 
-        let mut query = query_as(query_str);
+    pub fn and_group(self, group: impl FnOnce(Filter<T>) -> Filter<T>) -> Self {
+        self.edit_filter(|f| f.and_group(group))
+    }
+
+    pub fn order_by(mut self, field: Field<T>, order: Order) -> Self {
+        self.order.push((field, order));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
 
-        for (_, bind) in self.binds.drain(..) {
-            query = bind(query);
+    pub fn offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Includes soft deleted rows
+    pub fn with_deleted(mut self) -> Self {
+        self.deleted = Deleted::Include;
+        self
+    }
+
+    /// Returns only soft deleted rows
+    pub fn only_deleted(mut self) -> Self {
+        self.deleted = Deleted::Only;
+        self
+    }
+
+    /// Full text search in `Entity::search_fields` using the generated
+    /// `search` column. `text` is parsed with `websearch_to_tsquery` so any
+    /// user input is valid: `"tomato soup"`, `"\"tomato soup\" or salad
+    /// -chicken"`. Results are ordered by rank before other ordering.
+    pub fn search(mut self, text: impl ToString) -> Self {
+        self.search = Some(Search {
+            vector:   None,
+            function: "websearch_to_tsquery",
+            query:    text.to_string(),
+        });
+        self
+    }
+
+    /// Same as `search` but `query` has `to_tsquery` syntax: `"rust & (sercli
+    /// | axum)"`, `"wal:*"`. Invalid syntax fails the request.
+    pub fn search_query(mut self, query: impl ToString) -> Self {
+        self.search = Some(Search {
+            vector:   None,
+            function: "to_tsquery",
+            query:    query.to_string(),
+        });
+        self
+    }
+
+    /// Full text search of `text` in `fields` without generated column and
+    /// index
+    pub fn search_in(mut self, fields: &[Field<T>], text: impl ToString) -> Self {
+        self.search = Some(Search {
+            vector:   Some(search_vector(fields)),
+            function: "websearch_to_tsquery",
+            query:    text.to_string(),
+        });
+        self
+    }
+
+    /// Keyset pagination. Returns rows after `value` in `field` ordering.
+    /// If `field` is not ordered with `order_by` ascending order is used.
+    pub fn after<V>(mut self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.cursor = Some((
+            field,
+            Box::new(move |filter, order| match order {
+                Order::Asc => filter.gt(field, value),
+                Order::Desc => filter.lt(field, value),
+            }),
+        ));
+        self
+    }
+
+    fn apply_cursor(&mut self) {
+        let Some((field, apply)) = self.cursor.take() else {
+            return;
+        };
+
+        let order = if let Some((_, order)) = self.order.iter().find(|(f, _)| *f == field) {
+            *order
+        } else {
+            self.order.push((field, Order::Asc));
+            Order::Asc
+        };
+
+        let filter = replace(&mut self.filter, Filter::all());
+        self.filter = apply(filter, order);
+    }
+
+    /// `index` is the number of the last used bind parameter
+    fn where_clause(&self, index: &mut usize) -> String {
+        let mut conditions = vec![];
+
+        if !self.filter.is_empty() {
+            conditions.push(self.filter.to_sql(index));
+        }
+
+        // Search query is bound after filter values
+        if let Some(search) = &self.search {
+            *index += 1;
+            conditions.push(format!(
+                "{} @@ {}('{}', ${index})",
+                search.vector(),
+                search.function,
+                T::search_config()
+            ));
+        }
+
+        if let Some(deleted) = self.deleted.to_sql::<T>() {
+            conditions.push(deleted.to_string());
+        }
+
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {} ", conditions.join(" AND "))
+        }
+    }
+
+    fn prepare_string_query(&self) -> Result<String> {
+        let mut index = 0;
+
+        let mut query = format!(
+            "SELECT * FROM {} {}",
+            T::qualified_table_name(),
+            self.where_clause(&mut index)
+        );
+
+        let mut order: Vec<_> = self
+            .search
+            .iter()
+            .map(|search| {
+                format!(
+                    "ts_rank({}, {}('{}', ${index})) DESC",
+                    search.vector(),
+                    search.function,
+                    T::search_config()
+                )
+            })
+            .collect();
+
+        order.extend(
+            self.order
+                .iter()
+                .map(|(field, order)| format!("{} {}", T::column_name(field), order.to_sql())),
+        );
+
+        if !order.is_empty() {
+            let order = order.join(", ");
+
+            write!(query, "ORDER BY {order} ")?;
+        }
+
+        // Postgres accepts only `BIGINT` values
+        if let Some(limit) = self.limit {
+            if i64::try_from(limit).is_err() {
+                bail!("Limit {limit} is out of range")
+            }
+
+            write!(query, "LIMIT {limit} ")?;
+        }
+
+        if let Some(offset) = self.offset {
+            if i64::try_from(offset).is_err() {
+                bail!("Offset {offset} is out of range")
+            }
+
+            write!(query, "OFFSET {offset} ")?;
         }
 
         Ok(query)
     }
 
+    fn prepare_aggregate_query(&self, select: &str) -> String {
+        format!(
+            "SELECT {select} FROM {} {}",
+            T::qualified_table_name(),
+            self.where_clause(&mut 0)
+        )
+    }
+
+    fn prepare_arguments(&mut self) -> Result<PgArguments> {
+        let filter = replace(&mut self.filter, Filter::all());
+
+        let mut arguments = PgArguments::default();
+
+        for bind in filter.binds() {
+            bind(&mut arguments).map_err(|e| anyhow!(e))?;
+        }
+
+        if let Some(search) = &self.search {
+            if search.vector.is_none() && T::search_fields().is_empty() {
+                bail!("{} has no search fields", T::type_name())
+            }
+
+            arguments.add(search.query.clone()).map_err(|e| anyhow!(e))?;
+        }
+
+        Ok(arguments)
+    }
+
+    fn prepare_query(&mut self) -> Result<(String, PgArguments)> {
+        self.apply_cursor();
+        let query = self.prepare_string_query()?;
+        Ok((query, self.prepare_arguments()?))
+    }
+
     pub async fn one(mut self) -> Result<Option<T>> {
-        Ok(self.prepare_query()?.fetch_optional(self.pool).await?)
+        let (query, arguments) = self.prepare_query()?;
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_as_with(&query, arguments).fetch_optional(&mut *conn).await?)
     }
 
     pub async fn all(mut self) -> Result<Vec<T>> {
-        Ok(self.prepare_query()?.fetch_all(self.pool).await?)
+        let (query, arguments) = self.prepare_query()?;
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_as_with(&query, arguments).fetch_all(&mut *conn).await?)
+    }
+
+    /// Yields rows one by one as they are fetched instead of buffering the
+    /// whole result set in memory
+    pub fn stream(mut self) -> impl Stream<Item = Result<T>> + Send
+    where T: Send {
+        try_stream! {
+            let (query, arguments) = self.prepare_query()?;
+            let mut conn = self.db.acquire().await?;
+
+            let mut rows = query_as_with(&query, arguments).fetch(&mut *conn);
+
+            while let Some(row) = rows.try_next().await? {
+                yield row;
+            }
+        }
+    }
+
+    /// Zero based page of `size` rows with total number of rows matching the
+    /// filter
+    pub async fn page(mut self, page: u64, size: u64) -> Result<Page<T>> {
+        let Some(offset) = page.checked_mul(size) else {
+            bail!("Page {page} of size {size} is out of range")
+        };
+
+        self.limit = Some(size);
+        self.offset = Some(offset);
+
+        self.apply_cursor();
+
+        let count_query = self.prepare_aggregate_query("COUNT(*)");
+        let query = self.prepare_string_query()?;
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        let total: i64 = query_scalar_with(&count_query, arguments.clone()).fetch_one(&mut *conn).await?;
+        let items = query_as_with(&query, arguments).fetch_all(&mut *conn).await?;
+
+        Ok(Page {
+            items,
+            total: total.try_into()?,
+            page,
+            size,
+        })
+    }
+
+    pub async fn count(mut self) -> Result<u64> {
+        self.apply_cursor();
+        let query = self.prepare_aggregate_query("COUNT(*)");
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        let count: i64 = query_scalar_with(&query, arguments).fetch_one(&mut *conn).await?;
+
+        Ok(count.try_into()?)
+    }
+
+    pub async fn exists(mut self) -> Result<bool> {
+        self.apply_cursor();
+        let query = format!("SELECT EXISTS({})", self.prepare_aggregate_query("1"));
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_scalar_with(&query, arguments).fetch_one(&mut *conn).await?)
+    }
+
+    /// Deletes all rows matching the filter and returns number of deleted rows
+    /// Soft deletes matching rows of soft delete entities and deletes them
+    /// otherwise. Returns number of deleted rows.
+    pub async fn delete(mut self) -> Result<u64> {
+        if !T::soft_delete() {
+            return self.force_delete().await;
+        }
+
+        self.apply_cursor();
+
+        let table = T::qualified_table_name();
+
+        let mut assignments = vec!["deleted_at = now()".to_string()];
+        assignments.extend(managed_updates::<T>(&table));
+
+        let query = format!(
+            "UPDATE {table} SET {} {}",
+            assignments.join(", "),
+            self.where_clause(&mut 0)
+        );
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_with(&query, arguments).execute(&mut *conn).await?.rows_affected())
+    }
+
+    /// Deletes matching rows even if entity uses soft delete
+    pub async fn force_delete(mut self) -> Result<u64> {
+        self.apply_cursor();
+        let query = format!(
+            "DELETE FROM {} {}",
+            T::qualified_table_name(),
+            self.where_clause(&mut 0)
+        );
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_with(&query, arguments).execute(&mut *conn).await?.rows_affected())
+    }
+
+    /// Sets `field` to `value` in all rows matching the filter and returns
+    /// number of updated rows
+    pub async fn update_set<V>(mut self, field: Field<T>, value: V) -> Result<u64>
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.apply_cursor();
+
+        let mut index = 0;
+        let where_clause = self.where_clause(&mut index);
+
+        let table = T::qualified_table_name();
+
+        let mut assignments = vec![format!("{} = ${}", T::column_name(&field), index + 1)];
+        assignments.extend(managed_updates::<T>(&table));
+
+        let query = format!("UPDATE {table} SET {} {where_clause}", assignments.join(", "));
+
+        let mut arguments = self.prepare_arguments()?;
+        arguments.add(value).map_err(|e| anyhow!(e))?;
+
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_with(&query, arguments).execute(&mut *conn).await?.rows_affected())
+    }
+
+    /// Result type must match the type Postgres returns for the aggregate.
+    /// E.g. `SUM` of `INTEGER` column is `BIGINT` and `AVG` of it is `NUMERIC`.
+    /// `None` is returned if no rows match the filter.
+    pub async fn sum<V>(self, field: Field<T>) -> Result<Option<V>>
+    where V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres> + Send + Unpin {
+        self.aggregate("SUM", field).await
+    }
+
+    pub async fn min<V>(self, field: Field<T>) -> Result<Option<V>>
+    where V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres> + Send + Unpin {
+        self.aggregate("MIN", field).await
+    }
+
+    pub async fn max<V>(self, field: Field<T>) -> Result<Option<V>>
+    where V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres> + Send + Unpin {
+        self.aggregate("MAX", field).await
+    }
+
+    pub async fn avg<V>(self, field: Field<T>) -> Result<Option<V>>
+    where V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres> + Send + Unpin {
+        self.aggregate("AVG", field).await
+    }
+
+    async fn aggregate<V>(mut self, function: &str, field: Field<T>) -> Result<Option<V>>
+    where V: for<'r> sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres> + Send + Unpin {
+        self.apply_cursor();
+        let query = self.prepare_aggregate_query(&format!("{function}({})", T::column_name(&field)));
+        let arguments = self.prepare_arguments()?;
+
+        let mut conn = self.db.acquire().await?;
+
+        Ok(query_scalar_with(&query, arguments).fetch_one(&mut *conn).await?)
     }
 }
 
-fn prepare_string_query<T: Entity>(binds: &[Bind<T>]) -> Result<String> {
-    let mut query = format!("SELECT * FROM {} ", T::table_name());
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use reflected::Reflected;
+    use sqlx::FromRow;
 
-    for (i, (field, _)) in binds.iter().enumerate() {
-        if i == 0 {
-            write!(query, "WHERE {} = ${} ", field.name, i + 1)?;
-        } else {
-            write!(query, "AND {} = ${} ", field.name, i + 1)?;
+    use crate::{Crud, DateTime, Entity, server::crud::Order};
+
+    #[derive(Debug, Default, Reflected, FromRow)]
+    struct Rat {
+        id:   i32,
+        name: String,
+        age:  i32,
+    }
+
+    impl Entity for Rat {}
+
+    #[tokio::test]
+    async fn operators() -> Result<()> {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        let request = Rat::get(&pool)
+            .gt(Rat::AGE, 5)
+            .ilike(Rat::NAME, "%jer%")
+            .is_not_null(Rat::NAME)
+            .in_list(Rat::ID, [1, 2, 3])
+            .ne(Rat::AGE, 10);
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE age > $1 AND name ILIKE $2 AND name IS NOT NULL AND id = ANY($3) AND \
+             age <> $4 "
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn groups() -> Result<()> {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        let request = Rat::get(&pool)
+            .or(|f| f.with(Rat::NAME, "a").with(Rat::AGE, 1))
+            .with(Rat::ID, 3)
+            .or(|f| f.is_null(Rat::NAME).and_group(|f| f.gt(Rat::AGE, 5).lt(Rat::AGE, 10)))
+            .or(|f| f);
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE (name = $1 OR age = $2) AND id = $3 AND (name IS NULL OR (age > $4 \
+             AND age < $5)) "
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn soft_deleted_rows() -> Result<()> {
+        #[derive(Default, Reflected, FromRow)]
+        struct Mole {
+            id:         i32,
+            name:       String,
+            deleted_at: Option<DateTime>,
         }
+
+        impl Entity for Mole {
+            fn soft_delete() -> bool {
+                true
+            }
+        }
+
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        assert_eq!(
+            Mole::get(&pool).prepare_string_query()?,
+            "SELECT * FROM moles WHERE deleted_at IS NULL "
+        );
+
+        assert_eq!(
+            Mole::get(&pool).with(Mole::NAME, "a").only_deleted().prepare_string_query()?,
+            "SELECT * FROM moles WHERE name = $1 AND deleted_at IS NOT NULL "
+        );
+
+        assert_eq!(
+            Mole::get(&pool).with_deleted().prepare_string_query()?,
+            "SELECT * FROM moles "
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn search() -> Result<()> {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        let request = Rat::get(&pool)
+            .gt(Rat::AGE, 5)
+            .search_in(&[Rat::NAME], "jerry")
+            .order_by(Rat::ID, Order::Asc);
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE age > $1 AND to_tsvector('simple'::regconfig, coalesce(name::TEXT, \
+             '')) @@ websearch_to_tsquery('simple', $2) ORDER BY ts_rank(to_tsvector('simple'::regconfig, \
+             coalesce(name::TEXT, '')), websearch_to_tsquery('simple', $2)) DESC, id ASC "
+        );
+
+        let error = Rat::get(&pool)
+            .search("jerry")
+            .prepare_arguments()
+            .expect_err("Search without search fields");
+        assert!(error.to_string().contains("Rat has no search fields"));
+
+        Ok(())
     }
 
-    Ok(query)
+    #[tokio::test]
+    async fn oversized_page() -> Result<()> {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        let error = Rat::get(&pool).page(u64::MAX, 2).await.expect_err("Page offset overflowed");
+        assert!(error.to_string().contains("out of range"));
+
+        let error = Rat::get(&pool)
+            .page(0, u64::MAX)
+            .await
+            .expect_err("Page size doesn't fit in BIGINT");
+        assert!(error.to_string().contains("Limit"));
+
+        let error = Rat::get(&pool)
+            .page(1 << 32, 1 << 31)
+            .await
+            .expect_err("Page offset doesn't fit in BIGINT");
+        assert!(error.to_string().contains("Offset"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ordering() -> Result<()> {
+        let pool = sqlx::PgPool::connect_lazy("postgres://localhost")?;
+
+        let request = Rat::get(&pool)
+            .with(Rat::NAME, "a")
+            .order_by(Rat::AGE, Order::Desc)
+            .limit(10)
+            .offset(20);
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE name = $1 ORDER BY age DESC LIMIT 10 OFFSET 20 "
+        );
+
+        let mut request = Rat::get(&pool).after(Rat::ID, 5).with(Rat::NAME, "a").limit(2);
+
+        request.apply_cursor();
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE name = $1 AND id > $2 ORDER BY id ASC LIMIT 2 "
+        );
+
+        assert_eq!(
+            Rat::get(&pool).with(Rat::NAME, "a").prepare_aggregate_query("SUM(age)"),
+            "SELECT SUM(age) FROM rats WHERE name = $1 "
+        );
+
+        let mut request = Rat::get(&pool).after(Rat::AGE, 5).order_by(Rat::AGE, Order::Desc);
+
+        request.apply_cursor();
+
+        assert_eq!(
+            request.prepare_string_query()?,
+            "SELECT * FROM rats WHERE age < $1 ORDER BY age DESC "
+        );
+
+        Ok(())
+    }
 }
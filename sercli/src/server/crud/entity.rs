@@ -1,90 +1,512 @@
+use std::fmt::Write;
+
+use anyhow::{Result, anyhow, bail};
 use inflector::Inflector;
 use reflected::{Field, Reflected, Type};
 use sqlx::{FromRow, postgres::PgRow};
 
+use crate::server::crud::{Column, EntityId, PrimaryKey, Uuid};
+
+/// Entity stored in its own table.
+/// Implement with `impl Entity for MyType {}` and override methods to
+/// configure the table.
 pub trait Entity: Sized + Reflected + for<'r> FromRow<'r, PgRow> + Unpin {
-    fn table_name() -> String;
-    fn create_table_query() -> String;
-    fn insert_query() -> String;
-}
+    /// Column constraints emitted by `create_table_query`
+    fn columns() -> Vec<Column<Self>> {
+        vec![]
+    }
 
-impl<T: Reflected + for<'r> FromRow<'r, PgRow> + Unpin> Entity for T {
     fn table_name() -> String {
         Self::type_name().to_plural().to_snake_case()
     }
 
-    fn create_table_query() -> String {
-        let table_name = Self::table_name();
+    fn schema() -> Option<&'static str> {
+        None
+    }
 
-        if T::fields().is_empty() {
-            return format!(
-                r"CREATE TABLE IF NOT EXISTS {table_name}
-(
-   id SERIAL PRIMARY KEY
-);"
-            );
+    /// Type of `id` field. `Uuid` ids are generated by Postgres.
+    fn primary_key() -> PrimaryKey {
+        PrimaryKey::default()
+    }
+
+    /// Name of primary key column. The struct field is always called `id`,
+    /// use `#[sqlx(rename = "...")]` on it to map it to a different column.
+    fn id_column() -> &'static str {
+        "id"
+    }
+
+    /// Table name with schema prefix used in queries
+    fn qualified_table_name() -> String {
+        match Self::schema() {
+            Some(schema) => format!("{schema}.{}", Self::table_name()),
+            None => Self::table_name(),
+        }
+    }
+
+    fn column_name(field: &Field<Self>) -> &'static str {
+        if field.is_id() {
+            Self::id_column()
+        } else {
+            field.name
+        }
+    }
+
+    /// Adds `created_at` and `updated_at` columns set to `now()` by inserts
+    /// and updates. Entity must have `DateTime` fields with these names.
+    fn timestamps() -> bool {
+        false
+    }
+
+    /// Adds `version` column incremented by every update. Updates of rows
+    /// changed since they were read fail with `ConcurrencyError`. Entity must
+    /// have `i32` field `version`.
+    fn versioned() -> bool {
+        false
+    }
+
+    /// `Crud::delete` sets `deleted_at` column instead of deleting the row.
+    /// Requests skip deleted rows unless `with_deleted` or `only_deleted` is
+    /// used. Entity must have `Option<DateTime>` field `deleted_at`.
+    fn soft_delete() -> bool {
+        false
+    }
+
+    /// Text fields combined into generated `search` `tsvector` column with
+    /// GIN index. The column is used by `CrudRequest::search`.
+    fn search_fields() -> Vec<Field<Self>> {
+        vec![]
+    }
+
+    /// Text search configuration used for `tsvector` and `tsquery`
+    fn search_config() -> &'static str {
+        "simple"
+    }
+
+    /// Fields of composite primary key. Entities with composite key have no
+    /// `id` field and are loaded with `Crud::find` by values of these fields.
+    fn key() -> Vec<Field<Self>> {
+        vec![]
+    }
+
+    /// Values of `key` fields or `entity_id` if the key is not composite
+    fn key_values(&self) -> Result<Vec<EntityId>> {
+        let key = Self::key();
+
+        if key.is_empty() {
+            return Ok(vec![self.entity_id()?]);
+        }
+
+        key.into_iter().map(|field| id_value(self, field)).collect()
+    }
+
+    /// Fields of primary key: `key` fields or `id` field if the key is not
+    /// composite
+    fn key_fields() -> Vec<Field<Self>> {
+        let key = Self::key();
+
+        if !key.is_empty() {
+            return key;
+        }
+
+        Self::fields().iter().filter(|field| field.name == "id").copied().collect()
+    }
+
+    /// `id` field. Entities with composite key don't have it.
+    fn id_field() -> Result<Field<Self>> {
+        if !Self::key().is_empty() {
+            bail!("{} has composite key and no id field", Self::type_name())
+        }
+
+        Self::fields()
+            .iter()
+            .find(|field| field.name == "id")
+            .copied()
+            .ok_or_else(|| anyhow!("{} has no id field", Self::type_name()))
+    }
+
+    /// `WHERE` condition matching primary key bound starting from `$first`
+    fn key_condition(first: usize) -> String {
+        let key = Self::key();
+
+        if key.is_empty() {
+            return format!("{} = ${first}", Self::id_column());
         }
 
-        let mut fields = String::new();
+        key.iter()
+            .enumerate()
+            .map(|(i, field)| format!("{} = ${}", Self::column_name(field), first + i))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    /// Value of `id` field
+    fn entity_id(&self) -> Result<EntityId> {
+        id_value(self, Self::id_field()?)
+    }
+
+    /// Value of `Uuid` id or foreign key `field`. `Reflected` can't read
+    /// `Uuid` fields so entities with them return these fields here:
+    ///
+    /// ```ignore
+    /// fn uuid_value(&self, field: Field<Self>) -> Option<Uuid> {
+    ///     match field.name {
+    ///         "id" => Some(self.id),
+    ///         "planet_id" => Some(self.planet_id),
+    ///         _ => None,
+    ///     }
+    /// }
+    /// ```
+    fn uuid_value(&self, _field: Field<Self>) -> Option<Uuid> {
+        None
+    }
+
+    fn create_table_query() -> String {
+        let qualified_name = Self::qualified_table_name();
+        let schema = Self::schema()
+            .map(|schema| format!("CREATE SCHEMA IF NOT EXISTS {schema};\n"))
+            .unwrap_or_default();
+
+        let columns = Self::columns();
+        let key = Self::key();
+
+        let mut lines = vec![];
+
+        if key.is_empty() {
+            lines.push(format!("{} {}", Self::id_column(), Self::primary_key().to_sql()));
+        }
 
-        for field in T::fields() {
+        for field in Self::fields() {
             if field.is_id() {
                 continue;
             }
-            fields.push_str(&field_to_sql(field));
+            lines.push(column_definition(field, &columns));
         }
 
-        fields.pop();
-        fields.pop();
+        if let Some(search) = search_column::<Self>() {
+            lines.push(search);
+        }
+
+        if !key.is_empty() {
+            let key: Vec<_> = key.iter().map(Self::column_name).collect();
+            lines.push(format!("PRIMARY KEY ({})", key.join(", ")));
+        }
+
+        let mut query = format!(
+            "{schema}CREATE TABLE IF NOT EXISTS {qualified_name}\n(\n   {}\n);",
+            lines.join(",\n   ")
+        );
+
+        for column in columns.iter().filter(|column| column.index) {
+            query.push('\n');
+            query.push_str(&index_query::<Self>(Self::column_name(&column.field), None));
+        }
+
+        if search_column::<Self>().is_some() {
+            query.push('\n');
+            query.push_str(&index_query::<Self>("search", Some("GIN")));
+        }
+
+        query
+    }
+
+    fn insert_query() -> String {
+        let (columns, placeholders) = insert_columns::<Self>();
 
         format!(
-            r"CREATE TABLE IF NOT EXISTS {table_name}
-(
-   id SERIAL PRIMARY KEY,
-{fields}
-);"
+            "INSERT INTO {} ({}) VALUES ({}) RETURNING *;",
+            Self::qualified_table_name(),
+            columns,
+            placeholders
         )
     }
 
-    fn insert_query() -> String {
-        let fields: Vec<_> = T::fields().iter().filter(|f| !f.is_id()).collect();
+    fn insert_many_query(rows: usize) -> String {
+        let (columns, _) = insert_columns::<Self>();
 
-        let columns: Vec<_> = fields.iter().map(|field| field.name.to_string()).collect();
-        let columns = columns.join(", ");
+        let fields: Vec<_> = Self::fields().iter().filter(|f| !f.is_id()).collect();
+        let fields_count = fields.len();
 
-        let placeholders = (1..=fields.len()).map(|i| format!("${i}")).collect::<Vec<String>>().join(", ");
+        let values = (0..rows)
+            .map(|row| {
+                let placeholders = fields
+                    .iter()
+                    .enumerate()
+                    .map(|(i, field)| insert_value::<Self>(field, row * fields_count + i + 1))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!("({placeholders})")
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
 
         format!(
-            "INSERT INTO {} ({}) VALUES ({}) RETURNING *;",
-            T::table_name(),
+            "INSERT INTO {} ({}) VALUES {} RETURNING *;",
+            Self::qualified_table_name(),
             columns,
-            placeholders
+            values
         )
     }
-}
 
-fn field_to_sql<T>(field: &'static Field<T>) -> String {
-    format!("   {} {},\n", field.name, sql_type_from_field(field))
+    fn upsert_query(conflict_fields: &[Field<Self>]) -> String {
+        let (columns, placeholders) = insert_columns::<Self>();
+
+        let conflict = conflict_fields.iter().map(Self::column_name).collect::<Vec<_>>();
+
+        let mut updated: Vec<_> = Self::fields()
+            .iter()
+            .filter(|f| !f.is_id() && !conflict.contains(&f.name) && !is_managed::<Self>(f))
+            .map(|f| f.name)
+            .collect();
+
+        // `DO NOTHING` doesn't return existing row so conflicting
+        // columns are set to the same values in this case
+        if updated.is_empty() {
+            updated.clone_from(&conflict);
+        }
+
+        let mut updated: Vec<_> = updated.iter().map(|name| format!("{name} = EXCLUDED.{name}")).collect();
+        updated.extend(managed_updates::<Self>(&Self::qualified_table_name()));
+        let updated = updated.join(", ");
+
+        format!(
+            "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {} RETURNING *;",
+            Self::qualified_table_name(),
+            columns,
+            placeholders,
+            conflict.join(", "),
+            updated
+        )
+    }
+
+    fn update_query() -> Result<String> {
+        let fields: Vec<_> = Self::fields().iter().filter(|f| !f.is_id()).copied().collect();
+        Self::update_fields_query(&fields)
+    }
+
+    /// All non id fields are bound in the same order as in `insert_query`.
+    /// Only requested fields are referenced in `SET` so the same bind can be
+    /// used for partial updates.
+    fn update_fields_query(fields: &[Field<Self>]) -> Result<String> {
+        let bound: Vec<_> = Self::fields().iter().filter(|f| !f.is_id()).collect();
+
+        let position = |name: &str| {
+            bound
+                .iter()
+                .position(|f| f.name == name)
+                .map(|position| position + 1)
+                .ok_or_else(|| anyhow!("Field {name} is not in {}", Self::type_name()))
+        };
+
+        if Self::timestamps() {
+            position("updated_at")?;
+        }
+
+        let key = Self::key();
+
+        let mut assignments = vec![];
+
+        for field in fields {
+            if !field.is_id() && !key.contains(field) && !is_managed::<Self>(field) {
+                assignments.push(format!("{} = ${}", field.name, position(field.name)?));
+            }
+        }
+
+        assignments.extend(managed_updates::<Self>(&Self::qualified_table_name()));
+
+        // Composite key fields are already bound with the rest of the fields
+        let mut condition = if key.is_empty() {
+            format!("{} = ${}", Self::id_column(), bound.len() + 1)
+        } else {
+            let mut conditions = vec![];
+
+            for field in &key {
+                conditions.push(format!("{} = ${}", field.name, position(field.name)?));
+            }
+
+            conditions.join(" AND ")
+        };
+
+        if Self::versioned() {
+            write!(condition, " AND version = ${}", position("version")?)?;
+        }
+
+        Ok(format!(
+            "UPDATE {} SET {} WHERE {condition} RETURNING *;",
+            Self::qualified_table_name(),
+            assignments.join(", "),
+        ))
+    }
 }
 
-fn sql_type_from_field<T>(field: &'static Field<T>) -> String {
-    match field.tp {
-        Type::Float => "REAL NOT NULL".into(),
-        Type::Integer => "INTEGER NOT NULL".into(),
-        Type::Text => "VARCHAR(255) NOT NULL".into(),
-        Type::Enum => {
-            format!("{} NOT NULL", field.type_name.to_snake_case())
+/// Reads id or foreign key `field` of `entity`. Type of the id is taken from
+/// the field.
+pub(crate) fn id_value<T: Entity>(entity: &T, field: Field<T>) -> Result<EntityId> {
+    let key_type = match field.type_name {
+        "ID" | "i32" => PrimaryKey::Serial,
+        "i64" => PrimaryKey::BigSerial,
+        "Uuid" => {
+            return entity.uuid_value(field).map(Into::into).ok_or_else(|| {
+                anyhow!(
+                    "{}.{} is not returned by Entity::uuid_value",
+                    T::type_name(),
+                    field.name
+                )
+            });
         }
-        _ => unimplemented!(),
+        type_name => bail!(
+            "{}.{} of type {type_name} can't be used as id",
+            T::type_name(),
+            field.name
+        ),
+    };
+
+    key_type.parse(&entity.get_value(field))
+}
+
+fn insert_columns<T: Entity>() -> (String, String) {
+    let fields: Vec<_> = T::fields().iter().filter(|f| !f.is_id()).collect();
+
+    let columns: Vec<_> = fields.iter().map(|field| field.name.to_string()).collect();
+    let columns = columns.join(", ");
+
+    let placeholders = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| insert_value::<T>(field, i + 1))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    (columns, placeholders)
+}
+
+/// Value of column set by database instead of the bound field
+pub(crate) fn managed_value<T: Entity>(field: &Field<T>) -> Option<&'static str> {
+    match field.name {
+        "created_at" | "updated_at" if T::timestamps() => Some("now()"),
+        "version" if T::versioned() => Some("1"),
+        "deleted_at" if T::soft_delete() => Some("NULL"),
+        _ => None,
+    }
+}
+
+fn is_managed<T: Entity>(field: &Field<T>) -> bool {
+    managed_value(field).is_some()
+}
+
+fn insert_value<T: Entity>(field: &Field<T>, index: usize) -> String {
+    managed_value(field).map_or_else(|| format!("${index}"), ToString::to_string)
+}
+
+pub(crate) fn managed_updates<T: Entity>(table: &str) -> Vec<String> {
+    let mut updates = vec![];
+
+    if T::timestamps() {
+        updates.push("updated_at = now()".to_string());
+    }
+
+    if T::versioned() {
+        updates.push(format!("version = {table}.version + 1"));
+    }
+
+    updates
+}
+
+/// `CREATE INDEX` of `{table}_{column}_idx`
+pub(crate) fn index_query<T: Entity>(column: &str, method: Option<&str>) -> String {
+    let table_name = T::table_name();
+    let qualified_name = T::qualified_table_name();
+    let method = method.map(|method| format!("USING {method} ")).unwrap_or_default();
+
+    format!("CREATE INDEX IF NOT EXISTS {table_name}_{column}_idx ON {qualified_name} {method}({column});")
+}
+
+/// `tsvector` of `fields` joined with spaces
+pub(crate) fn search_vector<T: Entity>(fields: &[Field<T>]) -> String {
+    let text = fields
+        .iter()
+        .map(|field| format!("coalesce({}::TEXT, '')", T::column_name(field)))
+        .collect::<Vec<_>>()
+        .join(" || ' ' || ");
+
+    format!("to_tsvector('{}'::regconfig, {text})", T::search_config())
+}
+
+/// Definition of generated `search` column if entity has search fields
+pub(crate) fn search_column<T: Entity>() -> Option<String> {
+    let fields = T::search_fields();
+
+    if fields.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "search TSVECTOR GENERATED ALWAYS AS ({}) STORED",
+        search_vector(&fields)
+    ))
+}
+
+/// `name TYPE constraints` of the column as in `create_table_query`
+pub(crate) fn column_definition<T: Entity>(field: &'static Field<T>, columns: &[Column<T>]) -> String {
+    let column = columns.iter().find(|column| column.field.name == field.name);
+
+    let mut constraints = column.map(Column::constraints_sql).unwrap_or_default();
+
+    // `deleted_at` is inserted as `NULL` and needs no default
+    if let Some(value) = managed_value(field).filter(|value| *value != "NULL") {
+        write!(constraints, " DEFAULT {value}").unwrap();
+    }
+    let varchar = column.and_then(|column| column.varchar);
+
+    format!(
+        "{} {}{constraints}",
+        field.name,
+        sql_type_from_field(field, varchar)
+    )
+}
+
+fn sql_type_from_field<T>(field: &'static Field<T>, varchar: Option<u32>) -> String {
+    let tp = column_type(field, varchar);
+
+    if field.optional {
+        tp
+    } else {
+        format!("{tp} NOT NULL")
+    }
+}
+
+/// SQL type of the column without nullability
+pub(crate) fn column_type<T>(field: &'static Field<T>, varchar: Option<u32>) -> String {
+    match field.non_optional().tp {
+        Type::Float => match field.type_name {
+            "f64" => "DOUBLE PRECISION".into(),
+            _ => "REAL".into(),
+        },
+        Type::Integer => match field.type_name {
+            "i8" | "u8" | "i16" => "SMALLINT".into(),
+            "u32" | "i64" | "u64" | "isize" | "usize" => "BIGINT".into(),
+            _ => "INTEGER".into(),
+        },
+        Type::Text => varchar.map_or("TEXT".into(), |length| format!("VARCHAR({length})")),
+        Type::Bool => "BOOLEAN".into(),
+        Type::Decimal => "NUMERIC".into(),
+        Type::Date | Type::DateTime => "TIMESTAMP".into(),
+        Type::Duration => "INTERVAL".into(),
+        Type::Enum => match field.type_name {
+            "Bytes" => "BYTEA".into(),
+            "Uuid" => "UUID".into(),
+            type_name => type_name.to_snake_case(),
+        },
+        Type::Optional(_) => unreachable!("non_optional field can't have optional type"),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use reflected::{Reflected, ToReflectedVal};
+    use reflected::{Field, Reflected, ToReflectedVal};
+    use rust_decimal::Decimal;
     use sqlx::FromRow;
 
-    use crate::Entity;
+    use crate::{Bytes, Column, DateTime, Duration, Entity, EntityId, PrimaryKey, Uuid};
 
     #[derive(
         strum::Display,
@@ -120,14 +542,18 @@ mod test {
         tp:     WalletType,
     }
 
+    impl Entity for Cat {}
+
     #[test]
     fn table_name() {
         #[derive(Default, Reflected, FromRow)]
         struct Cat {}
+        impl Entity for Cat {}
         assert_eq!(Cat::table_name(), "cats");
 
         #[derive(Default, Reflected, FromRow)]
         struct BigCat {}
+        impl Entity for BigCat {}
         assert_eq!(BigCat::table_name(), "big_cats");
 
         #[derive(Default, Reflected, FromRow)]
@@ -137,6 +563,8 @@ mod test {
             sss:  f32,
             tp:   WalletType,
         }
+
+        impl Entity for PremiumPackage {}
         assert_eq!(PremiumPackage::table_name(), "premium_packages");
 
         dbg!(PremiumPackage::create_table_query());
@@ -146,6 +574,7 @@ mod test {
     fn create_table_query() {
         #[derive(Default, Reflected, FromRow)]
         struct Empty {}
+        impl Entity for Empty {}
 
         println!("{}", Empty::create_table_query());
 
@@ -165,15 +594,446 @@ mod test {
 (
    id SERIAL PRIMARY KEY,
    age INTEGER NOT NULL,
-   name VARCHAR(255) NOT NULL,
+   name TEXT NOT NULL,
    weight REAL NOT NULL,
    tp wallet_type NOT NULL
 );"
         );
     }
 
+    #[test]
+    fn create_table_query_types() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Parcel {
+            small:     i16,
+            big:       i64,
+            precise:   f64,
+            fragile:   bool,
+            price:     Decimal,
+            sent:      DateTime,
+            delivered: Option<DateTime>,
+            // sqlx can encode but not decode `chrono::Duration`
+            #[sqlx(skip)]
+            transit:   Duration,
+            note:      Option<String>,
+            weight:    Option<f32>,
+            label:     Bytes,
+        }
+
+        impl Entity for Parcel {}
+
+        assert_eq!(
+            Parcel::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS parcels
+(
+   id SERIAL PRIMARY KEY,
+   small SMALLINT NOT NULL,
+   big BIGINT NOT NULL,
+   precise DOUBLE PRECISION NOT NULL,
+   fragile BOOLEAN NOT NULL,
+   price NUMERIC NOT NULL,
+   sent TIMESTAMP NOT NULL,
+   delivered TIMESTAMP,
+   transit INTERVAL NOT NULL,
+   note TEXT,
+   weight REAL,
+   label BYTEA NOT NULL
+);"
+        );
+    }
+
+    #[test]
+    fn create_table_query_constraints() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Member {
+            email:    String,
+            nickname: Option<String>,
+            age:      i32,
+            score:    i32,
+            cat_id:   i32,
+        }
+
+        impl Entity for Member {
+            fn columns() -> Vec<Column<Self>> {
+                vec![
+                    Column::new(Member::EMAIL).unique().varchar(64),
+                    Column::new(Member::NICKNAME).varchar(32).index(),
+                    Column::new(Member::AGE).default("18").check("age >= 0"),
+                    Column::new(Member::SCORE).index(),
+                    Column::new(Member::CAT_ID).references::<Cat>(),
+                ]
+            }
+        }
+
+        assert_eq!(
+            Member::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS members
+(
+   id SERIAL PRIMARY KEY,
+   email VARCHAR(64) NOT NULL UNIQUE,
+   nickname VARCHAR(32),
+   age INTEGER NOT NULL DEFAULT 18 CHECK (age >= 0),
+   score INTEGER NOT NULL,
+   cat_id INTEGER NOT NULL REFERENCES cats (id)
+);
+CREATE INDEX IF NOT EXISTS members_nickname_idx ON members (nickname);
+CREATE INDEX IF NOT EXISTS members_score_idx ON members (score);"
+        );
+    }
+
+    #[test]
+    fn create_table_query_primary_key() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Invoice {
+            #[sqlx(rename = "invoice_id")]
+            id:     Uuid,
+            number: i64,
+        }
+
+        impl Entity for Invoice {
+            fn table_name() -> String {
+                "tbl_invoice".into()
+            }
+
+            fn schema() -> Option<&'static str> {
+                Some("billing")
+            }
+
+            fn primary_key() -> PrimaryKey {
+                PrimaryKey::Uuid
+            }
+
+            fn id_column() -> &'static str {
+                "invoice_id"
+            }
+
+            fn columns() -> Vec<Column<Self>> {
+                vec![Column::new(Invoice::NUMBER).index()]
+            }
+        }
+
+        assert_eq!(
+            Invoice::create_table_query(),
+            r"CREATE SCHEMA IF NOT EXISTS billing;
+CREATE TABLE IF NOT EXISTS billing.tbl_invoice
+(
+   invoice_id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+   number BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS tbl_invoice_number_idx ON billing.tbl_invoice (number);"
+        );
+
+        assert_eq!(
+            Invoice::update_query().unwrap(),
+            "UPDATE billing.tbl_invoice SET number = $1 WHERE invoice_id = $2 RETURNING *;"
+        );
+
+        #[derive(Default, Reflected, FromRow)]
+        struct Event {
+            id: i64,
+        }
+
+        impl Entity for Event {
+            fn primary_key() -> PrimaryKey {
+                PrimaryKey::BigSerial
+            }
+        }
+
+        assert_eq!(
+            Event::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS events
+(
+   id BIGSERIAL PRIMARY KEY
+);"
+        );
+    }
+
+    #[test]
+    fn composite_key() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Enrollment {
+            student_id: i32,
+            course_id:  i64,
+            grade:      Option<i32>,
+        }
+
+        impl Entity for Enrollment {
+            fn key() -> Vec<Field<Self>> {
+                vec![Enrollment::STUDENT_ID, Enrollment::COURSE_ID]
+            }
+        }
+
+        assert_eq!(
+            Enrollment::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS enrollments
+(
+   student_id INTEGER NOT NULL,
+   course_id BIGINT NOT NULL,
+   grade INTEGER,
+   PRIMARY KEY (student_id, course_id)
+);"
+        );
+
+        assert_eq!(
+            Enrollment::insert_query(),
+            "INSERT INTO enrollments (student_id, course_id, grade) VALUES ($1, $2, $3) RETURNING *;"
+        );
+
+        assert_eq!(
+            Enrollment::update_query().unwrap(),
+            "UPDATE enrollments SET grade = $3 WHERE student_id = $1 AND course_id = $2 RETURNING *;"
+        );
+
+        assert_eq!(Enrollment::key_condition(1), "student_id = $1 AND course_id = $2");
+
+        assert_eq!(
+            Enrollment {
+                student_id: 5,
+                course_id:  7,
+                grade:      None,
+            }
+            .key_values()
+            .unwrap(),
+            vec![EntityId::Int(5), EntityId::BigInt(7)]
+        );
+    }
+
+    #[test]
+    fn composite_key_of_unsupported_type() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Tag {
+            name:    String,
+            post_id: i32,
+        }
+
+        impl Entity for Tag {
+            fn key() -> Vec<Field<Self>> {
+                vec![Tag::NAME, Tag::POST_ID]
+            }
+        }
+
+        assert_eq!(
+            Tag::default().key_values().unwrap_err().to_string(),
+            "Tag.name of type String can't be used as id"
+        );
+    }
+
+    #[test]
+    fn search_column() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Article {
+            id:    i32,
+            title: String,
+            body:  Option<String>,
+        }
+
+        impl Entity for Article {
+            fn search_fields() -> Vec<Field<Self>> {
+                vec![Article::TITLE, Article::BODY]
+            }
+
+            fn search_config() -> &'static str {
+                "english"
+            }
+        }
+
+        assert_eq!(
+            Article::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS articles
+(
+   id SERIAL PRIMARY KEY,
+   title TEXT NOT NULL,
+   body TEXT,
+   search TSVECTOR GENERATED ALWAYS AS (to_tsvector('english'::regconfig, coalesce(title::TEXT, '') || ' ' || coalesce(body::TEXT, ''))) STORED
+);
+CREATE INDEX IF NOT EXISTS articles_search_idx ON articles USING GIN (search);"
+        );
+
+        assert_eq!(
+            Article::insert_query(),
+            "INSERT INTO articles (title, body) VALUES ($1, $2) RETURNING *;"
+        );
+    }
+
+    #[test]
+    fn managed_columns() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Note {
+            id:         i32,
+            text:       String,
+            created_at: DateTime,
+            updated_at: DateTime,
+            version:    i32,
+        }
+
+        impl Entity for Note {
+            fn timestamps() -> bool {
+                true
+            }
+
+            fn versioned() -> bool {
+                true
+            }
+        }
+
+        assert_eq!(
+            Note::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS notes
+(
+   id SERIAL PRIMARY KEY,
+   text TEXT NOT NULL,
+   created_at TIMESTAMP NOT NULL DEFAULT now(),
+   updated_at TIMESTAMP NOT NULL DEFAULT now(),
+   version INTEGER NOT NULL DEFAULT 1
+);"
+        );
+
+        assert_eq!(
+            Note::insert_query(),
+            "INSERT INTO notes (text, created_at, updated_at, version) VALUES ($1, now(), now(), 1) \
+             RETURNING *;"
+        );
+
+        assert_eq!(
+            Note::insert_many_query(2),
+            "INSERT INTO notes (text, created_at, updated_at, version) VALUES ($1, now(), now(), 1), ($5, \
+             now(), now(), 1) RETURNING *;"
+        );
+
+        assert_eq!(
+            Note::update_query().unwrap(),
+            "UPDATE notes SET text = $1, updated_at = now(), version = notes.version + 1 WHERE id = $5 AND \
+             version = $4 RETURNING *;"
+        );
+
+        assert_eq!(
+            Note::upsert_query(&[Note::TEXT]),
+            "INSERT INTO notes (text, created_at, updated_at, version) VALUES ($1, now(), now(), 1) ON \
+             CONFLICT (text) DO UPDATE SET text = EXCLUDED.text, updated_at = now(), version = \
+             notes.version + 1 RETURNING *;"
+        );
+    }
+
+    #[test]
+    fn soft_delete_column() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Letter {
+            id:         i32,
+            text:       String,
+            deleted_at: Option<DateTime>,
+        }
+
+        impl Entity for Letter {
+            fn soft_delete() -> bool {
+                true
+            }
+        }
+
+        assert_eq!(
+            Letter::create_table_query(),
+            r"CREATE TABLE IF NOT EXISTS letters
+(
+   id SERIAL PRIMARY KEY,
+   text TEXT NOT NULL,
+   deleted_at TIMESTAMP
+);"
+        );
+
+        assert_eq!(
+            Letter::insert_query(),
+            "INSERT INTO letters (text, deleted_at) VALUES ($1, NULL) RETURNING *;"
+        );
+
+        assert_eq!(
+            Letter::update_query().unwrap(),
+            "UPDATE letters SET text = $1 WHERE id = $3 RETURNING *;"
+        );
+    }
+
     #[test]
     fn insert_query() {
         println!("{}", Cat::insert_query());
     }
+
+    #[test]
+    fn insert_many_query() {
+        assert_eq!(
+            Cat::insert_many_query(1),
+            "INSERT INTO cats (age, name, weight, tp) VALUES ($1, $2, $3, $4) RETURNING *;"
+        );
+
+        assert_eq!(
+            Cat::insert_many_query(3),
+            "INSERT INTO cats (age, name, weight, tp) VALUES ($1, $2, $3, $4), ($5, $6, $7, $8), ($9, $10, \
+             $11, $12) RETURNING *;"
+        );
+    }
+
+    #[test]
+    fn upsert_query() {
+        assert_eq!(
+            Cat::upsert_query(&[Cat::NAME]),
+            "INSERT INTO cats (age, name, weight, tp) VALUES ($1, $2, $3, $4) ON CONFLICT (name) DO UPDATE \
+             SET age = EXCLUDED.age, weight = EXCLUDED.weight, tp = EXCLUDED.tp RETURNING *;"
+        );
+
+        assert_eq!(
+            Cat::upsert_query(&[Cat::AGE, Cat::NAME, Cat::WEIGHT, Cat::TP]),
+            "INSERT INTO cats (age, name, weight, tp) VALUES ($1, $2, $3, $4) ON CONFLICT (age, name, \
+             weight, tp) DO UPDATE SET age = EXCLUDED.age, name = EXCLUDED.name, weight = EXCLUDED.weight, \
+             tp = EXCLUDED.tp RETURNING *;"
+        );
+    }
+
+    #[test]
+    fn update_query() -> anyhow::Result<()> {
+        assert_eq!(
+            Cat::update_query()?,
+            "UPDATE cats SET age = $1, name = $2, weight = $3, tp = $4 WHERE id = $5 RETURNING *;"
+        );
+
+        assert_eq!(
+            Cat::update_fields_query(&[Cat::WEIGHT, Cat::AGE])?,
+            "UPDATE cats SET weight = $3, age = $1 WHERE id = $5 RETURNING *;"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn update_query_missing_managed_fields() {
+        #[derive(Default, Reflected, FromRow)]
+        struct Draft {
+            id:   i32,
+            text: String,
+        }
+
+        impl Entity for Draft {
+            fn versioned() -> bool {
+                true
+            }
+        }
+
+        #[derive(Default, Reflected, FromRow)]
+        struct Memo {
+            id:         i32,
+            text:       String,
+            created_at: DateTime,
+        }
+
+        impl Entity for Memo {
+            fn timestamps() -> bool {
+                true
+            }
+        }
+
+        assert_eq!(
+            Draft::update_query().unwrap_err().to_string(),
+            "Field version is not in Draft"
+        );
+        assert_eq!(
+            Memo::update_query().unwrap_err().to_string(),
+            "Field updated_at is not in Memo"
+        );
+    }
 }
@@ -0,0 +1,235 @@
+use reflected::Field;
+use sqlx::{Arguments, Postgres, error::BoxDynError, postgres::PgArguments};
+
+use crate::{
+    Entity,
+    server::crud::{EntityId, EntityIds},
+};
+
+pub(crate) type ArgBind = Box<dyn FnOnce(&mut PgArguments) -> Result<(), BoxDynError> + Send>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Gte,
+    Lte,
+    Like,
+    ILike,
+    In,
+    IsNull,
+    IsNotNull,
+}
+
+impl Operator {
+    fn to_sql(self, field: &str, index: usize) -> String {
+        match self {
+            Self::Eq => format!("{field} = ${index}"),
+            Self::Ne => format!("{field} <> ${index}"),
+            Self::Gt => format!("{field} > ${index}"),
+            Self::Lt => format!("{field} < ${index}"),
+            Self::Gte => format!("{field} >= ${index}"),
+            Self::Lte => format!("{field} <= ${index}"),
+            Self::Like => format!("{field} LIKE ${index}"),
+            Self::ILike => format!("{field} ILIKE ${index}"),
+            Self::In => format!("{field} = ANY(${index})"),
+            Self::IsNull => format!("{field} IS NULL"),
+            Self::IsNotNull => format!("{field} IS NOT NULL"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Joiner {
+    And,
+    Or,
+}
+
+impl Joiner {
+    fn to_sql(self) -> &'static str {
+        match self {
+            Self::And => " AND ",
+            Self::Or => " OR ",
+        }
+    }
+}
+
+struct Condition<T> {
+    field: Field<T>,
+    op:    Operator,
+    bind:  Option<ArgBind>,
+}
+
+enum Node<T> {
+    Condition(Condition<T>),
+    Group(Filter<T>),
+}
+
+/// Group of conditions joined with `AND` or `OR`.
+/// Groups can be nested to build expressions like `(a = 1 OR b = 2) AND c = 3`.
+pub struct Filter<T> {
+    joiner: Joiner,
+    nodes:  Vec<Node<T>>,
+}
+
+impl<T> Filter<T> {
+    pub(crate) fn all() -> Self {
+        Self {
+            joiner: Joiner::And,
+            nodes:  vec![],
+        }
+    }
+
+    fn any() -> Self {
+        Self {
+            joiner: Joiner::Or,
+            nodes:  vec![],
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.nodes.iter().all(|node| match node {
+            Node::Condition(_) => false,
+            Node::Group(group) => group.is_empty(),
+        })
+    }
+
+    fn push<V>(mut self, field: Field<T>, op: Operator, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.nodes.push(Node::Condition(Condition {
+            field,
+            op,
+            bind: Some(Box::new(move |args| args.add(value))),
+        }));
+        self
+    }
+
+    fn push_no_value(mut self, field: Field<T>, op: Operator) -> Self {
+        self.nodes.push(Node::Condition(Condition {
+            field,
+            op,
+            bind: None,
+        }));
+        self
+    }
+
+    fn push_group(mut self, group: Self) -> Self {
+        self.nodes.push(Node::Group(group));
+        self
+    }
+
+    pub fn with<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Eq, value)
+    }
+
+    pub fn ne<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Ne, value)
+    }
+
+    pub fn gt<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Gt, value)
+    }
+
+    pub fn lt<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Lt, value)
+    }
+
+    pub fn gte<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Gte, value)
+    }
+
+    pub fn lte<V>(self, field: Field<T>, value: V) -> Self
+    where V: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::Lte, value)
+    }
+
+    pub fn like(self, field: Field<T>, pattern: impl ToString) -> Self {
+        self.push(field, Operator::Like, pattern.to_string())
+    }
+
+    pub fn ilike(self, field: Field<T>, pattern: impl ToString) -> Self {
+        self.push(field, Operator::ILike, pattern.to_string())
+    }
+
+    pub fn in_list<V>(self, field: Field<T>, values: impl IntoIterator<Item = V>) -> Self
+    where Vec<V>: sqlx::Encode<'static, Postgres> + sqlx::Type<Postgres> + Send + 'static {
+        self.push(field, Operator::In, values.into_iter().collect::<Vec<V>>())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn is_null(self, field: Field<T>) -> Self {
+        self.push_no_value(field, Operator::IsNull)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn is_not_null(self, field: Field<T>) -> Self {
+        self.push_no_value(field, Operator::IsNotNull)
+    }
+
+    /// Adds a group where conditions are joined with `OR`
+    pub fn or(self, group: impl FnOnce(Self) -> Self) -> Self {
+        self.push_group(group(Self::any()))
+    }
+
+    /// Adds a group where conditions are joined with `AND`
+    pub fn and_group(self, group: impl FnOnce(Self) -> Self) -> Self {
+        self.push_group(group(Self::all()))
+    }
+
+    /// Like `in_list` for entity ids of any primary key type
+    pub fn in_ids(self, field: Field<T>, ids: impl IntoIterator<Item = impl Into<EntityId>>) -> Self {
+        self.push(
+            field,
+            Operator::In,
+            EntityIds(ids.into_iter().map(Into::into).collect()),
+        )
+    }
+
+    pub(crate) fn binds(self) -> Vec<ArgBind> {
+        let mut binds = vec![];
+
+        for node in self.nodes {
+            match node {
+                Node::Condition(condition) => binds.extend(condition.bind),
+                Node::Group(group) => binds.extend(group.binds()),
+            }
+        }
+
+        binds
+    }
+}
+
+impl<T: Entity> Filter<T> {
+    /// `index` is the number of the last used bind parameter.
+    /// It is shared between nested groups so parameters are numbered in the
+    /// same order as `binds` returns them.
+    pub(crate) fn to_sql(&self, index: &mut usize) -> String {
+        let mut parts = vec![];
+
+        for node in &self.nodes {
+            match node {
+                Node::Condition(condition) => {
+                    if condition.bind.is_some() {
+                        *index += 1;
+                    }
+                    parts.push(condition.op.to_sql(T::column_name(&condition.field), *index));
+                }
+                Node::Group(group) => {
+                    if group.is_empty() {
+                        continue;
+                    }
+                    parts.push(format!("({})", group.to_sql(index)));
+                }
+            }
+        }
+
+        parts.join(self.joiner.to_sql())
+    }
+}
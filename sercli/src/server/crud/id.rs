@@ -0,0 +1,223 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use derive_more::{Deref, From};
+use reflected::ToReflectedVal;
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    Encode, Postgres, Type,
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgHasArrayType, PgTypeInfo},
+};
+
+/// Type of entity primary key column
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PrimaryKey {
+    /// `SERIAL`, `i32` id
+    #[default]
+    Serial,
+    /// `BIGSERIAL`, `i64` id
+    BigSerial,
+    /// `UUID` generated by Postgres, `Uuid` id
+    Uuid,
+}
+
+impl PrimaryKey {
+    pub(crate) fn to_sql(self) -> &'static str {
+        match self {
+            Self::Serial => "SERIAL PRIMARY KEY",
+            Self::BigSerial => "BIGSERIAL PRIMARY KEY",
+            Self::Uuid => "UUID PRIMARY KEY DEFAULT gen_random_uuid()",
+        }
+    }
+
+    pub fn parse(self, id: &str) -> Result<EntityId> {
+        Ok(match self {
+            Self::Serial => EntityId::Int(id.parse()?),
+            Self::BigSerial => EntityId::BigInt(id.parse()?),
+            Self::Uuid => EntityId::Uuid(id.parse()?),
+        })
+    }
+}
+
+/// Value of entity primary key
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntityId {
+    Int(i32),
+    BigInt(i64),
+    Uuid(Uuid),
+}
+
+impl From<i32> for EntityId {
+    fn from(id: i32) -> Self {
+        Self::Int(id)
+    }
+}
+
+impl From<i64> for EntityId {
+    fn from(id: i64) -> Self {
+        Self::BigInt(id)
+    }
+}
+
+impl From<Uuid> for EntityId {
+    fn from(id: Uuid) -> Self {
+        Self::Uuid(id)
+    }
+}
+
+impl Display for EntityId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(id) => id.fmt(f),
+            Self::BigInt(id) => id.fmt(f),
+            Self::Uuid(id) => id.fmt(f),
+        }
+    }
+}
+
+impl Type<Postgres> for EntityId {
+    fn type_info() -> PgTypeInfo {
+        <i32 as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <i32 as Type<Postgres>>::compatible(ty)
+            || <i64 as Type<Postgres>>::compatible(ty)
+            || <Uuid as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for EntityId {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match self {
+            Self::Int(id) => <i32 as Encode<Postgres>>::encode_by_ref(id, buf),
+            Self::BigInt(id) => <i64 as Encode<Postgres>>::encode_by_ref(id, buf),
+            Self::Uuid(id) => <Uuid as Encode<Postgres>>::encode_by_ref(id, buf),
+        }
+    }
+
+    // Actual column type depends on the variant
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(match self {
+            Self::Int(_) => <i32 as Type<Postgres>>::type_info(),
+            Self::BigInt(_) => <i64 as Type<Postgres>>::type_info(),
+            Self::Uuid(_) => <Uuid as Type<Postgres>>::type_info(),
+        })
+    }
+}
+
+/// List of ids of the same type bound as Postgres array
+pub(crate) struct EntityIds(pub(crate) Vec<EntityId>);
+
+impl EntityIds {
+    fn typed<T>(&self, extract: impl Fn(&EntityId) -> Option<T>) -> Result<Vec<T>, BoxDynError> {
+        self.0
+            .iter()
+            .map(|id| extract(id).ok_or_else(|| "Ids of different types in one list".into()))
+            .collect()
+    }
+}
+
+impl Type<Postgres> for EntityIds {
+    fn type_info() -> PgTypeInfo {
+        <i32 as PgHasArrayType>::array_type_info()
+    }
+}
+
+impl Encode<'_, Postgres> for EntityIds {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        match self.0.first() {
+            None | Some(EntityId::Int(_)) => {
+                let ids = self.typed(|id| {
+                    if let EntityId::Int(id) = id {
+                        Some(*id)
+                    } else {
+                        None
+                    }
+                })?;
+                <Vec<i32> as Encode<Postgres>>::encode_by_ref(&ids, buf)
+            }
+            Some(EntityId::BigInt(_)) => {
+                let ids = self.typed(|id| {
+                    if let EntityId::BigInt(id) = id {
+                        Some(*id)
+                    } else {
+                        None
+                    }
+                })?;
+                <Vec<i64> as Encode<Postgres>>::encode_by_ref(&ids, buf)
+            }
+            Some(EntityId::Uuid(_)) => {
+                let ids = self.typed(|id| {
+                    if let EntityId::Uuid(id) = id {
+                        Some(*id)
+                    } else {
+                        None
+                    }
+                })?;
+                <Vec<Uuid> as Encode<Postgres>>::encode_by_ref(&ids, buf)
+            }
+        }
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(match self.0.first() {
+            None | Some(EntityId::Int(_)) => <i32 as PgHasArrayType>::array_type_info(),
+            Some(EntityId::BigInt(_)) => <i64 as PgHasArrayType>::array_type_info(),
+            Some(EntityId::Uuid(_)) => <Uuid as PgHasArrayType>::array_type_info(),
+        })
+    }
+}
+
+/// `uuid::Uuid` usable as `Reflected` entity field
+#[derive(
+    Debug,
+    Default,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    sqlx::Type,
+    Deref,
+    From,
+)]
+#[serde(transparent)]
+#[sqlx(transparent)]
+pub struct Uuid(uuid::Uuid);
+
+impl Uuid {
+    pub fn new_v4() -> Self {
+        Self(uuid::Uuid::new_v4())
+    }
+}
+
+impl Display for Uuid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(uuid::Uuid::from_str(s)?))
+    }
+}
+
+impl ToReflectedVal<Uuid> for &str {
+    fn to_reflected_val(&self) -> Result<Uuid, String> {
+        Uuid::from_str(self).map_err(|e| format!("Failed to parse {self}: {e}"))
+    }
+}
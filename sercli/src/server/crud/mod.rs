@@ -1,7 +1,21 @@
+mod bytes;
+mod column;
+mod concurrency_error;
 mod crud;
 mod crud_request;
 mod entity;
+mod filter;
+mod id;
+mod page;
+mod schema_check;
 
+pub use bytes::*;
+pub use column::*;
+pub use concurrency_error::*;
 pub use crud::*;
 pub use crud_request::*;
 pub use entity::*;
+pub use filter::*;
+pub use id::*;
+pub use page::*;
+pub use schema_check::*;
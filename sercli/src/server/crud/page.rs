@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: u64,
+    pub page:  u64,
+    pub size:  u64,
+}
+
+/// Zero based page number and page size
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pagination {
+    pub page: u64,
+    pub size: u64,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self { page: 0, size: 50 }
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use reflected::Type;
+use sqlx::{PgConnection, query_as, query_scalar};
+
+use crate::{
+    Entity,
+    server::{
+        PgAcquire,
+        crud::{column_definition, column_type, index_query, managed_value, search_column},
+    },
+};
+
+/// Difference between entity fields and its table in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaMismatch {
+    MissingTable {
+        table: String,
+    },
+    MissingColumn {
+        table:  String,
+        column: String,
+    },
+    ExtraColumn {
+        table:  String,
+        column: String,
+    },
+    TypeMismatch {
+        table:    String,
+        column:   String,
+        expected: String,
+        found:    String,
+    },
+    Nullability {
+        table:    String,
+        column:   String,
+        nullable: bool,
+    },
+    MissingEnumType {
+        table:     String,
+        column:    String,
+        type_name: String,
+    },
+}
+
+impl Display for SchemaMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingTable { table } => write!(f, "table {table} doesn't exist"),
+            Self::MissingColumn { table, column } => write!(f, "{table}.{column} doesn't exist"),
+            Self::ExtraColumn { table, column } => write!(f, "{table}.{column} has no entity field"),
+            Self::TypeMismatch {
+                table,
+                column,
+                expected,
+                found,
+            } => write!(f, "{table}.{column} has type {found} instead of {expected}"),
+            Self::Nullability {
+                table,
+                column,
+                nullable,
+            } => write!(
+                f,
+                "{table}.{column} is {} but entity field is {}",
+                if *nullable { "NOT NULL" } else { "nullable" },
+                if *nullable { "optional" } else { "not optional" },
+            ),
+            Self::MissingEnumType {
+                table,
+                column,
+                type_name,
+            } => write!(f, "enum type {type_name} of {table}.{column} doesn't exist"),
+        }
+    }
+}
+
+/// Entities don't match the database.
+/// Returned by `Crud::verify_schema` and `verify_schemas` wrapped in
+/// `anyhow::Error`: `error.downcast_ref::<SchemaError>()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    pub mismatches: Vec<SchemaMismatch>,
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Database schema doesn't match entities:")?;
+
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {mismatch}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+pub(crate) struct ExpectedColumn {
+    pub(crate) name:       &'static str,
+    pub(crate) id:         bool,
+    pub(crate) sql_type:   String,
+    pub(crate) nullable:   bool,
+    pub(crate) enum_type:  bool,
+    pub(crate) definition: String,
+    /// `DEFAULT` of the column fills existing rows when it's added
+    pub(crate) default:    bool,
+    /// `UNIQUE` or `REFERENCES` column can't be filled with the same value
+    pub(crate) constraint: bool,
+    /// `CREATE INDEX` of the column
+    pub(crate) index:      Option<String>,
+}
+
+/// Table layout expected by entity `T`: `EntitySchema::of::<User>()`
+pub struct EntitySchema {
+    table:                   String,
+    schema:                  Option<&'static str>,
+    pub(crate) columns:      Vec<ExpectedColumn>,
+    pub(crate) create_table: String,
+}
+
+impl EntitySchema {
+    pub fn of<T: Entity>() -> Self {
+        let columns = T::columns();
+
+        let mut expected: Vec<_> = T::fields()
+            .iter()
+            .map(|field| {
+                let column = columns.iter().find(|column| column.field.name == field.name);
+                let name = T::column_name(field);
+                let sql_type = column_type(field, column.and_then(|column| column.varchar));
+
+                ExpectedColumn {
+                    name,
+                    id: field.is_id(),
+                    enum_type: field.non_optional().tp == Type::Enum
+                        && !matches!(sql_type.as_str(), "BYTEA" | "UUID"),
+                    sql_type,
+                    nullable: field.optional,
+                    definition: column_definition(field, &columns),
+                    default: managed_value(field).is_some()
+                        || column.is_some_and(|column| column.default.is_some()),
+                    constraint: column.is_some_and(|column| column.unique || column.references.is_some()),
+                    index: column.filter(|column| column.index).map(|_| index_query::<T>(name, None)),
+                }
+            })
+            .collect();
+
+        if let Some(definition) = search_column::<T>() {
+            expected.push(ExpectedColumn {
+                name: "search",
+                id: false,
+                sql_type: "TSVECTOR".to_string(),
+                nullable: true,
+                enum_type: false,
+                definition,
+                default: false,
+                constraint: false,
+                index: Some(index_query::<T>("search", Some("GIN"))),
+            });
+        }
+
+        Self {
+            table:        T::table_name(),
+            schema:       T::schema(),
+            columns:      expected,
+            create_table: T::create_table_query(),
+        }
+    }
+
+    pub(crate) fn qualified_table_name(&self) -> String {
+        match self.schema {
+            Some(schema) => format!("{schema}.{}", self.table),
+            None => self.table.clone(),
+        }
+    }
+
+    async fn mismatches(&self, conn: &mut PgConnection) -> Result<Vec<SchemaMismatch>> {
+        let table = self.qualified_table_name();
+
+        let enum_types: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|column| column.enum_type)
+            .map(|column| column.sql_type.clone())
+            .collect();
+
+        let existing_enums: Vec<String> =
+            query_scalar("SELECT typname::TEXT FROM pg_type WHERE typtype = 'e' AND typname = ANY($1)")
+                .bind(&enum_types)
+                .fetch_all(&mut *conn)
+                .await?;
+
+        let mut mismatches: Vec<SchemaMismatch> = self
+            .columns
+            .iter()
+            .filter(|column| column.enum_type && !existing_enums.contains(&column.sql_type))
+            .map(|column| SchemaMismatch::MissingEnumType {
+                table:     table.clone(),
+                column:    column.name.to_string(),
+                type_name: column.sql_type.clone(),
+            })
+            .collect();
+
+        let actual: Vec<(String, String, String)> = query_as(
+            "SELECT column_name::TEXT, udt_name::TEXT, is_nullable::TEXT FROM information_schema.columns \
+             WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2 ORDER BY \
+             ordinal_position",
+        )
+        .bind(self.schema)
+        .bind(&self.table)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        if actual.is_empty() {
+            mismatches.push(SchemaMismatch::MissingTable { table });
+            return Ok(mismatches);
+        }
+
+        for expected in &self.columns {
+            let Some((_, udt_name, is_nullable)) = actual.iter().find(|(name, ..)| name == expected.name)
+            else {
+                mismatches.push(SchemaMismatch::MissingColumn {
+                    table:  table.clone(),
+                    column: expected.name.to_string(),
+                });
+                continue;
+            };
+
+            let missing_enum = expected.enum_type && !existing_enums.contains(&expected.sql_type);
+
+            if !missing_enum && !type_matches(&expected.sql_type, udt_name) {
+                mismatches.push(SchemaMismatch::TypeMismatch {
+                    table:    table.clone(),
+                    column:   expected.name.to_string(),
+                    expected: expected.sql_type.clone(),
+                    found:    udt_name.clone(),
+                });
+            }
+
+            let nullable = is_nullable == "YES";
+
+            if nullable != expected.nullable {
+                mismatches.push(SchemaMismatch::Nullability {
+                    table:    table.clone(),
+                    column:   expected.name.to_string(),
+                    nullable: expected.nullable,
+                });
+            }
+        }
+
+        for (name, ..) in &actual {
+            if !self.columns.iter().any(|column| column.name == name) {
+                mismatches.push(SchemaMismatch::ExtraColumn {
+                    table:  table.clone(),
+                    column: name.clone(),
+                });
+            }
+        }
+
+        Ok(mismatches)
+    }
+}
+
+/// Compares type from `create_table_query` with `udt_name` of
+/// `information_schema.columns`
+pub(crate) fn type_matches(expected: &str, udt_name: &str) -> bool {
+    let expected = match expected {
+        "SMALLINT" => "int2",
+        "INTEGER" => "int4",
+        "BIGINT" => "int8",
+        "REAL" => "float4",
+        "DOUBLE PRECISION" => "float8",
+        // `String` can be read from both
+        "TEXT" => return matches!(udt_name, "text" | "varchar"),
+        "BOOLEAN" => "bool",
+        "NUMERIC" => "numeric",
+        "TIMESTAMP" => "timestamp",
+        "INTERVAL" => "interval",
+        "BYTEA" => "bytea",
+        "UUID" => "uuid",
+        "TSVECTOR" => "tsvector",
+        varchar if varchar.starts_with("VARCHAR") => "varchar",
+        enum_type => enum_type,
+    };
+
+    expected == udt_name
+}
+
+/// Checks tables of all `schemas` and fails with `SchemaError` listing every
+/// mismatch found
+pub async fn verify_schemas(schemas: &[EntitySchema], db: impl PgAcquire) -> Result<()> {
+    let mut conn = db.acquire().await?;
+
+    let mut mismatches = vec![];
+
+    for schema in schemas {
+        mismatches.extend(schema.mismatches(&mut conn).await?);
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaError { mismatches }.into())
+    }
+}
@@ -1,13 +1,17 @@
 use anyhow::{Result, anyhow};
-use sqlx::{Executor, PgPool, query};
+use sqlx::{Executor, PgConnection, query};
+
+use crate::server::PgAcquire;
 
 pub struct DBStorage {}
 
 impl DBStorage {
-    pub async fn set(key: &str, data: &[u8], pool: &PgPool) -> Result<()> {
-        Self::create_table(pool).await?;
+    pub async fn set(key: &str, data: &[u8], db: impl PgAcquire) -> Result<()> {
+        let mut conn = db.acquire().await?;
+
+        Self::create_table(&mut conn).await?;
 
-        pool.execute(
+        conn.execute(
             query(
                 r"INSERT INTO key_value_storage (key, value)
               VALUES ($1, $2)
@@ -24,37 +28,41 @@ impl DBStorage {
         Ok(())
     }
 
-    pub async fn get(key: &str, pool: &PgPool) -> Result<Option<Vec<u8>>> {
-        Self::create_table(pool).await?;
+    pub async fn get(key: &str, db: impl PgAcquire) -> Result<Option<Vec<u8>>> {
+        let mut conn = db.acquire().await?;
+
+        Self::create_table(&mut conn).await?;
 
         let result: Option<(Vec<u8>,)> =
             sqlx::query_as("SELECT value FROM key_value_storage WHERE key = $1;")
                 .bind(key)
-                .fetch_optional(pool)
+                .fetch_optional(&mut *conn)
                 .await?;
 
         Ok(result.map(|(value,)| value))
     }
 
-    pub async fn del(key: &str, pool: &PgPool) -> Result<()> {
+    pub async fn del(key: &str, db: impl PgAcquire) -> Result<()> {
+        let mut conn = db.acquire().await?;
+
         sqlx::query("DELETE FROM key_value_storage WHERE key = $1;")
             .bind(key)
-            .execute(pool)
+            .execute(&mut *conn)
             .await?;
 
         Ok(())
     }
 
-    pub async fn set_str(key: &str, val: &str, pool: &PgPool) -> Result<()> {
-        Self::set(key, val.as_bytes(), pool).await
+    pub async fn set_str(key: &str, val: &str, db: impl PgAcquire) -> Result<()> {
+        Self::set(key, val.as_bytes(), db).await
     }
 
-    pub async fn get_str(key: &str, pool: &PgPool) -> Result<Option<String>> {
-        Ok(Self::get(key, pool).await?.map(|vec| String::from_utf8(vec).unwrap()))
+    pub async fn get_str(key: &str, db: impl PgAcquire) -> Result<Option<String>> {
+        Ok(Self::get(key, db).await?.map(|vec| String::from_utf8(vec).unwrap()))
     }
 
-    async fn create_table(pool: &PgPool) -> Result<()> {
-        pool.execute(query(
+    async fn create_table(conn: &mut PgConnection) -> Result<()> {
+        conn.execute(query(
             r"CREATE TABLE IF NOT EXISTS key_value_storage (
               key VARCHAR(255) PRIMARY KEY,
               value BYTEA NOT NULL
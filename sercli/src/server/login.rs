@@ -0,0 +1,130 @@
+use anyhow::{Result, anyhow, bail};
+use sqlx::PgPool;
+
+use crate::{Crud, Login, SercliUser, check_password};
+
+/// Argon2 hash checked when there is no user with requested login so response
+/// time doesn't tell if the login exists
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$JObmzhV5G6I16ehB+WWYcA$lRYGKQsTHntObP6EPUSeOPmRuNVhBdmDzl7D0KGiIe4";
+
+/// Same error for unknown login and wrong password
+pub(crate) const INVALID_CREDENTIALS: &str = "Invalid login or password";
+
+/// User with `login` in `SercliUser::login_field_name` field and matching
+/// password hash
+pub(crate) async fn authenticate<User: SercliUser>(login: &Login, pool: &PgPool) -> Result<User> {
+    let Some(field) = User::fields().iter().find(|field| field.name == User::login_field_name()) else {
+        bail!(
+            "{} has no login field {}",
+            std::any::type_name::<User>(),
+            User::login_field_name()
+        )
+    };
+
+    let user = User::get(pool).with(*field, login.login.clone()).one().await?;
+
+    let hash = user.as_ref().map_or(DUMMY_HASH, |user| user.password());
+
+    let valid = check_password(&login.password, hash).await.is_ok();
+
+    match user {
+        Some(user) if valid => Ok(user),
+        _ => Err(anyhow!(INVALID_CREDENTIALS)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use fake::{Fake, faker::internet::en::SafeEmail};
+    use reflected::Reflected;
+    use sqlx::FromRow;
+
+    use crate::{
+        Crud, Entity, EntityId, ID, Login, SercliUser,
+        db::prepare_db,
+        hash_password,
+        server::login::{INVALID_CREDENTIALS, authenticate},
+    };
+
+    #[derive(Debug, Default, Clone, Reflected, FromRow)]
+    struct Visitor {
+        id:       ID,
+        email:    String,
+        password: String,
+    }
+
+    impl Entity for Visitor {}
+
+    impl SercliUser for Visitor {
+        fn id(&self) -> EntityId {
+            self.id.into()
+        }
+
+        fn password(&self) -> &str {
+            &self.password
+        }
+
+        fn login(&self) -> &str {
+            &self.email
+        }
+
+        fn login_field_name() -> &'static str {
+            "email"
+        }
+    }
+
+    #[tokio::test]
+    async fn login() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Visitor::create_table(&pool).await?;
+
+        let email: String = SafeEmail().fake();
+
+        let visitor = Visitor {
+            id:       0,
+            email:    email.clone(),
+            password: hash_password("secret").await?,
+        }
+        .insert(&pool)
+        .await?;
+
+        let user: Visitor = authenticate(
+            &Login {
+                login:    email.clone(),
+                password: "secret".to_string(),
+            },
+            &pool,
+        )
+        .await?;
+
+        assert_eq!(user.id, visitor.id);
+
+        let wrong_password = authenticate::<Visitor>(
+            &Login {
+                login:    email,
+                password: "wrong".to_string(),
+            },
+            &pool,
+        )
+        .await
+        .expect_err("Wrong password accepted");
+
+        let unknown_login = authenticate::<Visitor>(
+            &Login {
+                login:    SafeEmail().fake(),
+                password: "secret".to_string(),
+            },
+            &pool,
+        )
+        .await
+        .expect_err("Unknown login accepted");
+
+        assert_eq!(wrong_password.to_string(), INVALID_CREDENTIALS);
+        assert_eq!(unknown_login.to_string(), INVALID_CREDENTIALS);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 mod access_token;
+mod acquire;
 mod authorize_request;
 mod authorized_user;
 mod compose;
@@ -6,10 +7,14 @@ pub(crate) mod crud;
 pub(crate) mod db_storage;
 mod errors_handling;
 mod handle;
+mod login;
 mod server;
+mod token_keys;
+mod transaction;
 
 use std::fmt::{Debug, Display, Formatter};
 
+pub use acquire::*;
 pub use authorize_request::*;
 pub use authorized_user::*;
 use axum::{
@@ -20,7 +25,9 @@ pub use compose::connection_string_from_compose;
 pub use errors_handling::*;
 pub use handle::*;
 pub use server::*;
+pub use token_keys::*;
 use tokio::task::JoinHandle;
+pub use transaction::*;
 
 use crate::db::prepare_db;
 
@@ -1,20 +1,35 @@
-use std::future::Future;
+use std::{
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
 
 use anyhow::Result;
-use axum::{Json, Router, extract::State, handler::Handler, routing::get};
+use axum::{Extension, Json, Router, extract::State, handler::Handler, routing::get};
 use serde::{Serialize, de::DeserializeOwned};
 use sqlx::PgPool;
 use tokio::{net::TcpListener, runtime::Runtime, spawn, sync::oneshot};
 
 use crate::{
-    SercliUser,
+    Entity, Login, REFRESH_TOKEN, SercliUser, TokenPair,
     client::Request,
-    server::{AppError, AuthorizeRequest, ServerHandle, authorized_user::AuthorizedUser, prepare_db},
+    server::{
+        AppError, AuthorizeRequest, KeySource, ServerHandle, TokenKeys,
+        access_token::{AccessToken, TokenLifetimes, TrustedProxies},
+        authorized_user::AuthorizedUser,
+        crud::{EntitySchema, verify_schemas},
+        login::authenticate,
+        prepare_db,
+    },
 };
 
 #[derive(Default)]
 pub struct Server {
-    router: Router<PgPool>,
+    router:    Router<PgPool>,
+    schemas:   Vec<EntitySchema>,
+    lifetimes: TokenLifetimes,
+    keys:      KeySource,
+    proxies:   TrustedProxies,
 }
 
 impl Server {
@@ -25,9 +40,67 @@ impl Server {
     pub fn edit_router(self, edit: impl FnOnce(Router<PgPool>) -> Router<PgPool>) -> Self {
         Self {
             router: edit(self.router),
+            ..self
         }
     }
 
+    /// Lifetime of access tokens. 15 minutes by default.
+    pub fn access_token_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetimes.access = lifetime;
+        self
+    }
+
+    /// Lifetime of refresh tokens. 30 days by default.
+    pub fn refresh_token_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetimes.refresh = lifetime;
+        self
+    }
+
+    /// Source of access token encryption keys. `KeySource::Database` by
+    /// default. Keys are loaded on start and passed to requests as
+    /// `Extension<TokenKeys>`.
+    pub fn access_token_keys(mut self, keys: KeySource) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Reverse proxy allowed to pass client address in `X-Forwarded-For`
+    /// header. Without trusted proxies the header is ignored and address of
+    /// the connection is stored in sessions.
+    pub fn trusted_proxy(mut self, address: IpAddr) -> Self {
+        self.proxies.0.push(address);
+        self
+    }
+
+    /// Registers `REFRESH_TOKEN` route exchanging refresh token for a new
+    /// `TokenPair`
+    pub fn add_token_refresh<User: SercliUser>(mut self) -> Self {
+        self.router = self
+            .router
+            .route(&format!("/{}", REFRESH_TOKEN.name), get(refresh_tokens::<User>));
+        self
+    }
+
+    /// Registers `request` signing in with `SercliUser::login_field_name` and
+    /// password. Starts new session and returns its tokens with `View` of the
+    /// user. `View` should have no password so its hash stays on the server.
+    pub fn add_login<User, View>(mut self, request: &'static Request<Login, (TokenPair, View)>) -> Self
+    where
+        User: SercliUser + Sync,
+        View: From<User> + Serialize + DeserializeOwned + Send + 'static,
+    {
+        self.router = self
+            .router
+            .route(&format!("/{}", request.name), get(login::<User, View>));
+        self
+    }
+
+    /// Fails server start if table of `T` doesn't match its fields
+    pub fn verify_schema<T: Entity>(mut self) -> Self {
+        self.schemas.push(EntitySchema::of::<T>());
+        self
+    }
+
     pub fn add_request<
         In: Serialize + DeserializeOwned + Send + 'static,
         Out: Serialize + DeserializeOwned + Send + 'static,
@@ -102,11 +175,26 @@ impl Server {
     async fn start_internal(self, started: Option<oneshot::Sender<ServerHandle>>) -> Result<()> {
         let listener = TcpListener::bind("0.0.0.0:8000").await?;
 
+        let pool = prepare_db().await?;
+
+        verify_schemas(&self.schemas, &pool).await?;
+
+        AccessToken::create_table(&pool).await?;
+
+        // Fails on start if keys from env or file are missing
+        let keys = TokenKeys::load(self.keys, &pool).await?;
+
         let (handle, receiver) = ServerHandle::new();
 
+        let router = self
+            .router
+            .layer(Extension(self.lifetimes))
+            .layer(Extension(keys))
+            .layer(Extension(self.proxies));
+
         let server = axum::serve(
             listener,
-            self.router.with_state(prepare_db().await?).into_make_service(),
+            router.with_state(pool).into_make_service_with_connect_info::<SocketAddr>(),
         )
         .with_graceful_shutdown(receiver);
 
@@ -122,3 +210,25 @@ impl Server {
         Ok(())
     }
 }
+
+async fn refresh_tokens<User: SercliUser>(
+    Extension(lifetimes): Extension<TokenLifetimes>,
+    Extension(keys): Extension<TokenKeys>,
+    State(pool): State<PgPool>,
+    Json(refresh_token): Json<String>,
+) -> Result<Json<TokenPair>, AppError> {
+    Ok(Json(
+        AccessToken::refresh::<User>(&refresh_token, lifetimes, &keys, &pool).await?,
+    ))
+}
+
+async fn login<User: SercliUser + Sync, View: From<User>>(
+    request: AuthorizeRequest<User>,
+    State(pool): State<PgPool>,
+    Json(login): Json<Login>,
+) -> Result<Json<(TokenPair, View)>, AppError> {
+    let user = authenticate::<User>(&login, &pool).await?;
+    let tokens = request.generate_tokens(&user).await?;
+
+    Ok(Json((tokens, user.into())))
+}
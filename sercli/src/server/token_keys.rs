@@ -0,0 +1,232 @@
+use std::{
+    env::var,
+    fs::read_to_string,
+    path::PathBuf,
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use anyhow::{Result, anyhow, bail};
+use pasetors::{
+    keys::{Generate, SymmetricKey},
+    paserk::{FormatAsPaserk, Id},
+    version4::V4,
+};
+use sqlx::{Executor, PgPool, query, query_scalar};
+
+use crate::{DBStorage, server::PgAcquire};
+
+/// Where access token encryption keys come from. Set with
+/// `Server::access_token_keys`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KeySource {
+    /// Keys are generated and stored in `access_token_keys` table. Managed with
+    /// `TokenKeys::rotate` and `TokenKeys::retire`.
+    #[default]
+    Database,
+    /// Environment variable with whitespace separated PASERK keys
+    /// (`k4.local.`) created by `TokenKeys::generate`. First key encrypts new
+    /// tokens, the rest are only used to decrypt tokens issued before.
+    Env(String),
+    /// File with keys in the same format as `KeySource::Env`
+    File(PathBuf),
+}
+
+pub(crate) struct TokenKey {
+    /// PASERK id stored in `kid` footer claim of tokens encrypted with this key
+    pub(crate) id:  String,
+    pub(crate) key: SymmetricKey<V4>,
+}
+
+impl TokenKey {
+    fn parse(paserk: &str) -> Result<Self> {
+        let key = SymmetricKey::<V4>::try_from(paserk)?;
+
+        Ok(Self {
+            id: to_paserk(&Id::from(&key))?,
+            key,
+        })
+    }
+}
+
+/// Encryption keys of access tokens loaded from `KeySource` on server start.
+/// Requests get them as `Extension<TokenKeys>`.
+#[derive(Clone)]
+pub struct TokenKeys {
+    source: KeySource,
+    keys:   Arc<RwLock<Vec<TokenKey>>>,
+}
+
+impl TokenKeys {
+    /// New random key in PASERK format for `KeySource::Env` and
+    /// `KeySource::File`
+    pub fn generate() -> Result<String> {
+        to_paserk(&SymmetricKey::<V4>::generate()?)
+    }
+
+    /// Keys of `source`. `KeySource::Database` keys table and its first key
+    /// are created here so requests only read them.
+    pub async fn load(source: KeySource, pool: &PgPool) -> Result<Self> {
+        if source == KeySource::Database {
+            Self::create_table(pool).await?;
+        }
+
+        let keys = Self::read(&source, pool).await?;
+
+        Ok(Self {
+            source,
+            keys: Arc::new(RwLock::new(keys)),
+        })
+    }
+
+    /// Reads keys from the source again. Keys rotated by other servers are
+    /// loaded on first token encrypted with them, keys retired by other
+    /// servers are dropped only by `refresh` or restart.
+    pub async fn refresh(&self, pool: &PgPool) -> Result<()> {
+        let keys = Self::read(&self.source, pool).await?;
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+
+    /// Makes new key active. Tokens encrypted with previous keys stay valid
+    /// until these keys are retired. Returns id of the new key.
+    pub async fn rotate(&self, pool: &PgPool) -> Result<String> {
+        self.check_database_source()?;
+
+        let id = Self::insert(&Self::generate()?, pool).await?;
+
+        self.refresh(pool).await?;
+
+        Ok(id)
+    }
+
+    /// Removes key so all tokens encrypted with it stop working. Use after
+    /// `rotate` if the key has leaked.
+    pub async fn retire(&self, key_id: &str, pool: &PgPool) -> Result<()> {
+        self.check_database_source()?;
+
+        self.refresh(pool).await?;
+
+        let ids = self.key_ids();
+
+        if ids.first().is_some_and(|id| id == key_id) {
+            bail!("Active key {key_id} can't be retired. Rotate keys first.")
+        }
+
+        if !ids.iter().any(|id| id == key_id) {
+            bail!("No key with id {key_id}")
+        }
+
+        pool.execute(query("DELETE FROM access_token_keys WHERE id = $1").bind(key_id))
+            .await?;
+
+        self.refresh(pool).await
+    }
+
+    /// Ids of loaded keys. First one is active.
+    pub fn key_ids(&self) -> Vec<String> {
+        self.keys().iter().map(|key| key.id.clone()).collect()
+    }
+
+    pub(crate) fn contains(&self, key_id: &str) -> bool {
+        self.keys().iter().any(|key| key.id == key_id)
+    }
+
+    /// Loaded keys. First one is used to encrypt new tokens.
+    pub(crate) fn keys(&self) -> RwLockReadGuard<'_, Vec<TokenKey>> {
+        self.keys.read().unwrap()
+    }
+
+    fn check_database_source(&self) -> Result<()> {
+        if self.source != KeySource::Database {
+            bail!("Keys of {:?} are rotated by editing the source", self.source)
+        }
+
+        Ok(())
+    }
+
+    async fn read(source: &KeySource, pool: &PgPool) -> Result<Vec<TokenKey>> {
+        let keys = match source {
+            KeySource::Database => {
+                let keys: Vec<String> =
+                    query_scalar("SELECT key FROM access_token_keys ORDER BY created_at DESC")
+                        .fetch_all(pool)
+                        .await?;
+                keys.join(" ")
+            }
+            KeySource::Env(name) => var(name).map_err(|e| anyhow!("Failed to read {name}: {e}"))?,
+            KeySource::File(path) => {
+                read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?
+            }
+        };
+
+        let keys: Vec<_> = keys.split_whitespace().map(TokenKey::parse).collect::<Result<_>>()?;
+
+        if keys.is_empty() {
+            bail!("No access token keys in {source:?}")
+        }
+
+        Ok(keys)
+    }
+
+    /// Creates `access_token_keys` with key stored in `key_value_storage`
+    /// before rotation was supported or a new one
+    async fn create_table(pool: &PgPool) -> Result<()> {
+        const LEGACY_STORAGE_KEY: &str = "access_token_encryption_key";
+
+        let mut tx = pool.begin().await?;
+
+        // Servers starting at the same time wait for the first one to create
+        // the initial key instead of creating one each
+        tx.execute(query(
+            "SELECT pg_advisory_xact_lock(hashtext('access_token_keys'))",
+        ))
+        .await?;
+
+        tx.execute(query(
+            r"CREATE TABLE IF NOT EXISTS access_token_keys (
+           id TEXT      PRIMARY KEY,
+          key TEXT      NOT NULL,
+   created_at TIMESTAMP NOT NULL DEFAULT clock_timestamp()
+);",
+        ))
+        .await?;
+
+        let has_keys: bool = query_scalar("SELECT EXISTS (SELECT FROM access_token_keys)")
+            .fetch_one(&mut *tx)
+            .await?;
+
+        if !has_keys {
+            let key = match DBStorage::get(LEGACY_STORAGE_KEY, &mut *tx).await? {
+                Some(data) => to_paserk(&SymmetricKey::<V4>::from(&data)?)?,
+                None => Self::generate()?,
+            };
+
+            Self::insert(&key, &mut *tx).await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn insert(paserk: &str, db: impl PgAcquire) -> Result<String> {
+        let key = TokenKey::parse(paserk)?;
+
+        let mut conn = db.acquire().await?;
+
+        conn.execute(
+            query("INSERT INTO access_token_keys (id, key) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING")
+                .bind(&key.id)
+                .bind(paserk),
+        )
+        .await?;
+
+        Ok(key.id)
+    }
+}
+
+fn to_paserk(value: &impl FormatAsPaserk) -> Result<String> {
+    let mut paserk = String::new();
+    value.fmt(&mut paserk)?;
+    Ok(paserk)
+}
@@ -0,0 +1,115 @@
+use std::pin::Pin;
+
+use anyhow::Result;
+use sqlx::{PgPool, Postgres, Transaction};
+
+pub type TransactionFuture<'t, R> = Pin<Box<dyn Future<Output = Result<R>> + Send + 't>>;
+
+/// Runs `body` inside a transaction.
+/// Commits if `body` returns `Ok` and rolls back on `Err`.
+///
+/// ```ignore
+/// let user = with_transaction(&pool, |tx| {
+///     Box::pin(async move {
+///         let user = user.insert(&mut *tx).await?;
+///         wallet.insert(&mut *tx).await?;
+///         Ok(user)
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn with_transaction<R, F>(pool: &PgPool, body: F) -> Result<R>
+where F: for<'t> FnOnce(&'t mut Transaction<'static, Postgres>) -> TransactionFuture<'t, R> {
+    let mut tx = pool.begin().await?;
+
+    match body(&mut tx).await {
+        Ok(result) => {
+            tx.commit().await?;
+            Ok(result)
+        }
+        Err(err) => {
+            tx.rollback().await?;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{Result, bail};
+    use reflected::Reflected;
+    use sqlx::FromRow;
+
+    use crate::{Crud, DBStorage, Entity, db::prepare_db, server::with_transaction};
+
+    #[derive(Debug, Clone, Default, PartialEq, Reflected, FromRow)]
+    struct Pigeon {
+        id:   i32,
+        name: String,
+    }
+
+    impl Entity for Pigeon {}
+
+    #[tokio::test]
+    async fn commit_and_rollback() -> Result<()> {
+        let pool = prepare_db().await?;
+
+        Pigeon::drop_table(&pool).await?;
+        Pigeon::create_table(&pool).await?;
+        DBStorage::del("pigeon_key", &pool).await?;
+
+        let pigeon = with_transaction(&pool, |tx| {
+            Box::pin(async move {
+                let pigeon = Pigeon {
+                    id:   0,
+                    name: "gulya".to_string(),
+                }
+                .insert(&mut *tx)
+                .await?;
+
+                DBStorage::set_str("pigeon_key", "gulya", &mut *tx).await?;
+
+                assert_eq!(Pigeon::get(&mut *tx).count().await?, 1);
+
+                Ok(pigeon)
+            })
+        })
+        .await?;
+
+        assert_eq!(Pigeon::get_all(&pool).await?, vec![pigeon.clone()]);
+        assert_eq!(
+            DBStorage::get_str("pigeon_key", &pool).await?,
+            Some("gulya".to_string())
+        );
+
+        let error = with_transaction::<(), _>(&pool, |tx| {
+            Box::pin(async move {
+                Pigeon {
+                    id:   0,
+                    name: "kesha".to_string(),
+                }
+                .insert(&mut *tx)
+                .await?;
+
+                Pigeon::get(&mut *tx).delete().await?;
+                DBStorage::del("pigeon_key", &mut *tx).await?;
+
+                bail!("Something went wrong")
+            })
+        })
+        .await
+        .expect_err("Transaction should have failed");
+
+        assert_eq!(format!("{error}"), "Something went wrong");
+
+        assert_eq!(Pigeon::get_all(&pool).await?, vec![pigeon]);
+        assert_eq!(
+            DBStorage::get_str("pigeon_key", &pool).await?,
+            Some("gulya".to_string())
+        );
+
+        Pigeon::drop_table(&pool).await?;
+
+        Ok(())
+    }
+}
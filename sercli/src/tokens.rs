@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::{DateTime, ID, client::Request};
+
+/// Short lived access token and long lived refresh token exchanged for a new
+/// pair with `REFRESH_TOKEN` request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token:  String,
+    pub refresh_token: String,
+}
+
+/// Route registered by `Server::add_token_refresh`
+pub const REFRESH_TOKEN: Request<String, TokenPair> = Request::new("refresh_token");
+
+/// Signed in device of a user listed by `AuthorizedUser::sessions`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub id:           ID,
+    pub created_at:   DateTime,
+    pub last_used_at: Option<DateTime>,
+    pub user_agent:   Option<String>,
+    pub ip:           Option<String>,
+    /// Taken from `device-name` header of the request creating the session
+    /// or set with `AuthorizedUser::name_session`
+    pub device_name:  Option<String>,
+    /// Session of the token used to list sessions
+    pub current:      bool,
+}
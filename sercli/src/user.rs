@@ -1,9 +1,9 @@
 use sqlx::{FromRow, postgres::PgRow};
 
-use crate::{Entity, ID};
+use crate::{Entity, EntityId};
 
 pub trait SercliUser: Entity + Clone + Send + Unpin + for<'r> FromRow<'r, PgRow> + 'static {
-    fn id(&self) -> ID;
+    fn id(&self) -> EntityId;
     fn password(&self) -> &str;
     fn login(&self) -> &str;
     fn login_field_name() -> &'static str;
@@ -1,4 +1,4 @@
-use model::{CREATE_WALLET, GET_USERS, GET_WALLETS, REGISTER};
+use model::{CREATE_WALLET, GET_USERS, GET_WALLETS, LOGIN, REGISTER, User, Wallet};
 use sercli::server::Server;
 
 use crate::{
@@ -8,7 +8,11 @@ use crate::{
 
 pub fn make_server() -> Server {
     Server::new()
+        .verify_schema::<User>()
+        .verify_schema::<Wallet>()
+        .add_token_refresh::<User>()
         .add_authorize_request(&REGISTER, handle_register)
+        .add_login::<User, _>(&LOGIN)
         .add_authorized_request(&GET_USERS, get_users)
         .add_authorized_request(&CREATE_WALLET, create_wallet)
         .add_authorized_request(&GET_WALLETS, get_wallets)
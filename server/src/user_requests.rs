@@ -1,27 +1,47 @@
 use axum::{Json, extract::State};
-use model::User;
+use model::{PublicUser, User, Wallet, WalletType};
 use sercli::{
-    Crud,
+    Crud, Decimal, TokenPair, hash_password,
     server::{AppError, AuthorizeRequest, AuthorizedUser},
+    with_transaction,
 };
 use sqlx::PgPool;
 
 pub async fn handle_register(
     request: AuthorizeRequest<User>,
     db: State<PgPool>,
-    user: Json<User>,
-) -> Result<Json<(String, User)>, AppError> {
-    let user = user.0.insert(&db).await?;
+    Json(mut user): Json<User>,
+) -> Result<Json<(TokenPair, PublicUser)>, AppError> {
+    user.password = hash_password(&user.password).await?;
 
-    let token = request.generate_token(&user).await?;
+    let user = with_transaction(&db, |tx| {
+        Box::pin(async move {
+            let user = user.insert(&mut *tx).await?;
 
-    Ok(Json((token, user)))
+            Wallet {
+                id:      0,
+                user_id: user.id,
+                name:    "Main".to_string(),
+                amount:  Decimal::ZERO,
+                tp:      WalletType::Fiat,
+            }
+            .insert(&mut *tx)
+            .await?;
+
+            Ok(user)
+        })
+    })
+    .await?;
+
+    let tokens = request.generate_tokens(&user).await?;
+
+    Ok(Json((tokens, user.into())))
 }
 
 pub async fn get_users(
     _user: AuthorizedUser<User>,
     db: State<PgPool>,
     _: Json<()>,
-) -> Result<Json<Vec<User>>, AppError> {
-    Ok(Json(User::get_all(&db).await?))
+) -> Result<Json<Vec<PublicUser>>, AppError> {
+    Ok(Json(User::get_all(&*db).await?.into_iter().map(Into::into).collect()))
 }
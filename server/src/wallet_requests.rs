@@ -1,11 +1,13 @@
 use axum::{Json, extract::State};
 use model::{User, Wallet};
 use sercli::{
-    Crud,
+    Crud, Order, Page, Pagination,
     server::{AppError, AuthorizedUser},
 };
 use sqlx::PgPool;
 
+const MAX_PAGE_SIZE: u64 = 100;
+
 pub async fn create_wallet(
     user: AuthorizedUser<User>,
     db: State<PgPool>,
@@ -15,7 +17,7 @@ pub async fn create_wallet(
 
     wallet.user_id = user.id;
 
-    let wallet = wallet.insert(&db).await?;
+    let wallet = wallet.insert(&*db).await?;
 
     Ok(Json(wallet))
 }
@@ -23,13 +25,17 @@ pub async fn create_wallet(
 pub async fn get_wallets(
     user: AuthorizedUser<User>,
     db: State<PgPool>,
-    _: Json<()>,
-) -> Result<Json<Vec<Wallet>>, AppError> {
+    pagination: Json<Pagination>,
+) -> Result<Json<Page<Wallet>>, AppError> {
     // Weird issue:
     // = note: this is a known limitation that will be removed in the future (see issue #100013 <https://github.com/rust-lang/rust/issues/100013> for more information)
     // let wallets = Wallet::FIELDS.user_id.all_where(user.id, &db).await?;
 
-    let wallets = Wallet::get(&db).with(Wallet::USER_ID, user.id).all().await?;
+    let wallets = Wallet::get(&*db)
+        .with(Wallet::USER_ID, user.id)
+        .order_by(Wallet::ID, Order::Asc)
+        .page(pagination.page, pagination.size.min(MAX_PAGE_SIZE))
+        .await?;
 
     Ok(Json(wallets))
 }